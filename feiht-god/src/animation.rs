@@ -0,0 +1,256 @@
+// Frame based sprite animation, the clips are read from .anim.ron files (see
+// assets/animations/player.anim.ron) and played on any entity with an Animator and a
+// TextureAtlasSprite. A frame can name an event that is sent when the frame is shown,
+// for things like footstep sounds or the moment a sword swing should hit.
+
+use bevy::{prelude::*, asset::{AssetLoader, LoadContext, LoadedAsset, BoxedFuture}, reflect::TypeUuid, utils::HashMap};
+use serde::Deserialize;
+
+use crate::GameState;
+
+pub struct AnimationPlugin;
+
+#[derive(Deserialize, Clone, Debug)]
+#[serde(deny_unknown_fields)]
+pub struct ClipFrame {
+    // which sprite of the sprite sheet to show
+    pub index: usize,
+    // how long to show this frame for in seconds, the frame_duration of the clip when left out
+    #[serde(default)]
+    pub duration: Option<f32>,
+    // sent as an AnimationEvent when the frame is shown
+    #[serde(default)]
+    pub event: Option<String>,
+}
+
+fn looping_default() -> bool {
+    true
+}
+
+#[derive(Deserialize, Clone, Debug)]
+#[serde(deny_unknown_fields)]
+pub struct SpriteClip {
+    pub frames: Vec<ClipFrame>,
+    // how long every frame is shown for in seconds, unless the frame says otherwise
+    pub frame_duration: f32,
+    // looping clips start over after the last frame, the others stop on it
+    #[serde(default = "looping_default")]
+    pub looping: bool,
+}
+
+impl SpriteClip {
+    fn frame_duration(&self, frame: usize) -> f32 {
+        self.frames[frame].duration.unwrap_or(self.frame_duration)
+    }
+}
+
+// every clip of one thing (like the player) by name
+#[derive(Deserialize, TypeUuid, Clone, Debug)]
+#[uuid = "c4f1a8e3-2b6d-4e97-a05c-7d3b9e1f6a28"]
+#[serde(deny_unknown_fields)]
+pub struct SpriteAnimations {
+    pub clips: HashMap<String, SpriteClip>,
+}
+
+impl SpriteAnimations {
+    // clips with no frames or frames that take no time would never get anywhere
+    fn validate(&self) -> Result<(), String> {
+        for (name, clip) in self.clips.iter() {
+            if clip.frames.is_empty() {
+                return Err(format!("clip \"{}\" has no frames", name));
+            }
+            if clip.frame_duration <= 0.0 || clip.frames.iter().any(|frame| frame.duration.is_some_and(|duration| duration <= 0.0)) {
+                return Err(format!("clip \"{}\" has a frame that is not shown for any time", name));
+            }
+        }
+        Ok(())
+    }
+}
+
+#[derive(Default)]
+struct SpriteAnimationsLoader;
+
+impl AssetLoader for SpriteAnimationsLoader {
+    fn load<'a>(
+        &'a self,
+        bytes: &'a [u8],
+        load_context: &'a mut LoadContext,
+    ) -> BoxedFuture<'a, Result<(), anyhow::Error>> {
+        Box::pin(async move {
+            let animations: SpriteAnimations = ron::de::from_bytes(bytes)?;
+            animations.validate().map_err(|error| anyhow::anyhow!("{}, {}", load_context.path().display(), error))?;
+            load_context.set_default_asset(LoadedAsset::new(animations));
+            Ok(())
+        })
+    }
+
+    fn extensions(&self) -> &[&str] {
+        &["anim.ron"]
+    }
+}
+
+// plays clips from a SpriteAnimations asset on the TextureAtlasSprite of the entity
+#[derive(Component)]
+pub struct Animator {
+    pub animations: Handle<SpriteAnimations>,
+    clip: String,
+    frame: usize,
+    // how long the current frame has been shown for
+    elapsed: f32,
+    // the frame that was last put on the sprite, None when the clip has just been changed
+    shown: Option<usize>,
+    // 1 is normal speed, 2 plays the clip twice as fast
+    pub speed: f32,
+}
+
+impl Animator {
+    pub fn new(animations: Handle<SpriteAnimations>, clip: &str) -> Self {
+        Animator {
+            animations,
+            clip: clip.to_string(),
+            frame: 0,
+            elapsed: 0.0,
+            shown: None,
+            speed: 1.0,
+        }
+    }
+
+    // switch to another clip from the start, playing the clip that is already playing does nothing
+    pub fn play(&mut self, clip: &str) {
+        if self.clip == clip { return; }
+        self.clip = clip.to_string();
+        self.frame = 0;
+        self.elapsed = 0.0;
+        self.shown = None;
+    }
+
+    // the frame of the clip that is being shown, counting from 0
+    pub fn frame(&self) -> usize {
+        self.frame
+    }
+}
+
+// sent when an animator shows a frame that has an event
+pub struct AnimationEvent {
+    pub entity: Entity,
+    pub name: String,
+}
+
+impl Plugin for AnimationPlugin {
+    fn build(&self, app: &mut App) {
+        app
+            .add_asset::<SpriteAnimations>()
+            .init_asset_loader::<SpriteAnimationsLoader>()
+            .add_event::<AnimationEvent>()
+            // animations run after everything has picked which clip to play this frame
+            .add_system_to_stage(CoreStage::PostUpdate, animate_sprites);
+    }
+}
+
+fn animate_sprites(
+    // query for every animated entity, the TextureAtlasSprite is what gets changed to show the frame
+    mut animator_query: Query<(Entity, &mut Animator, &mut TextureAtlasSprite)>,
+    animations: Res<Assets<SpriteAnimations>>,
+    mut events: EventWriter<AnimationEvent>,
+    // everything stands still while the game is paused
+    state: Res<State<GameState>>,
+    time: Res<Time>
+) {
+    if *state.current() == GameState::Pause { return; }
+
+    for (entity, mut animator, mut sprite) in animator_query.iter_mut() {
+        // the clips might still be loading
+        let clip = match animations
+            .get(&animator.animations)
+            .and_then(|animations| animations.clips.get(&animator.clip))
+        {
+            Some(clip) => clip,
+            None => continue,
+        };
+
+        // the clip can be changed without play (which starts it over), so the frame could be past its end
+        if animator.frame >= clip.frames.len() {
+            animator.frame = 0;
+        }
+
+        if animator.shown.is_some() {
+            animator.elapsed += time.delta_seconds() * animator.speed;
+        }
+
+        // go through every frame that has finished since last time, a long frame
+        // (or a fast animation) can skip more than one
+        while animator.elapsed >= clip.frame_duration(animator.frame) {
+            let last_frame = animator.frame + 1 == clip.frames.len();
+            // one shot clips stay on the last frame once they are done
+            if last_frame && !clip.looping {
+                animator.elapsed = 0.0;
+                break;
+            }
+
+            animator.elapsed -= clip.frame_duration(animator.frame);
+            animator.frame = if last_frame { 0 } else { animator.frame + 1 };
+
+            if let Some(event) = &clip.frames[animator.frame].event {
+                events.send(AnimationEvent { entity, name: event.clone() });
+            }
+        }
+
+        if animator.shown != Some(animator.frame) {
+            // the first frame of a clip sends its event when the clip starts
+            if animator.shown.is_none() {
+                if let Some(event) = &clip.frames[animator.frame].event {
+                    events.send(AnimationEvent { entity, name: event.clone() });
+                }
+            }
+            sprite.index = clip.frames[animator.frame].index;
+            animator.shown = Some(animator.frame);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::sprites::Facing;
+
+    fn animations(text: &str) -> SpriteAnimations {
+        let animations: SpriteAnimations = ron::de::from_str(text).unwrap();
+        animations.validate().unwrap();
+        animations
+    }
+
+    #[test]
+    fn player_has_looping_clips_for_every_direction() {
+        let animations = animations(include_str!("../assets/animations/player.anim.ron"));
+        assert!(animations.clips.values().all(|clip| clip.looping));
+        let directions = [
+            Facing::Right, Facing::UpRight, Facing::Up, Facing::UpLeft,
+            Facing::Left, Facing::DownLeft, Facing::Down, Facing::DownRight,
+        ];
+        for facing in directions {
+            for action in ["idle", "walk"] {
+                let clip = format!("{}_{}", action, facing.name());
+                assert!(animations.clips.contains_key(&clip), "no {} clip", clip);
+            }
+        }
+    }
+
+    #[test]
+    fn swing_clip_ends_on_its_last_frame() {
+        let animations = animations(include_str!("../assets/animations/sword.anim.ron"));
+        let swing = &animations.clips["swing"];
+        // a one shot clip stops on its last frame, so the end event is only sent once
+        assert!(!swing.looping);
+        let events: Vec<usize> = swing.frames.iter().enumerate().filter(|(_, frame)| frame.event.is_some()).map(|(i, _)| i).collect();
+        assert_eq!(events, vec![swing.frames.len() - 1]);
+        assert_eq!(swing.frames.last().unwrap().event.as_deref(), Some("swing_end"));
+    }
+
+    #[test]
+    fn clips_that_would_never_move_on_are_rejected() {
+        let no_frames: SpriteAnimations = ron::de::from_str("(clips: {\"walk\": (frames: [], frame_duration: 0.1)})").unwrap();
+        assert!(no_frames.validate().is_err());
+        let no_time: SpriteAnimations = ron::de::from_str("(clips: {\"walk\": (frames: [(index: 1, duration: Some(0.0))], frame_duration: 0.1)})").unwrap();
+        assert!(no_time.validate().is_err());
+    }
+}
@@ -0,0 +1,230 @@
+// Collision of the player against the walls of the map, the walls are only their positions.
+//
+// Movement is swept one axis at a time, the player moves as far as it can before touching
+// a wall so it ends up flush against it, and whatever movement is left on the other axis
+// still happens so the player slides along walls. Running into the very edge of a corner
+// pushes the player around it (like the old Zelda games) instead of stopping them dead.
+
+use bevy::math::Vec2;
+
+use crate::TILE_SIZE;
+
+// size of the player hitbox, a bit smaller than a tile so the player fits through one tile gaps
+pub const PLAYER_SIZE: f32 = TILE_SIZE * 0.9;
+// size of the hitbox of every wall
+pub const WALL_SIZE: f32 = TILE_SIZE;
+// if the player runs into a corner and overlaps the wall by at most this much
+// on the other axis they are pushed around the corner
+pub const CORNER_NUDGE: f32 = TILE_SIZE * 0.4;
+// walls closer than this are touching, this keeps rounding errors from letting the player into walls
+const CONTACT: f32 = 0.0001;
+
+// how far apart the centers of the player and a wall are when they are touching
+const TOUCHING: f32 = (PLAYER_SIZE + WALL_SIZE) / 2.0;
+
+// the result of moving along a single axis
+struct Sweep {
+    // how far the player moved, always in the direction of the movement
+    moved: f32,
+    // the walls that stopped the player, empty if the player moved the whole way
+    blockers: Vec<Vec2>,
+}
+
+// move the player along one axis (0 is x, 1 is y) until it touches a wall
+fn sweep(position: Vec2, axis: usize, amount: f32, walls: &[Vec2]) -> Sweep {
+    let other = 1 - axis;
+    let direction = amount.signum();
+    let mut sweep = Sweep { moved: amount.abs(), blockers: Vec::new() };
+
+    if amount == 0.0 {
+        return sweep;
+    }
+
+    for &wall in walls {
+        // walls that are not next to the player on the other axis can not be hit
+        if (wall[other] - position[other]).abs() >= TOUCHING - CONTACT { continue; }
+
+        // how far the player can move before touching the wall, walls behind the
+        // player (or ones it is already stuck in) are ignored so it can always get out
+        let gap = (wall[axis] - position[axis]) * direction - TOUCHING;
+        if gap < -CONTACT { continue; }
+        let gap = gap.max(0.0);
+
+        if gap < sweep.moved - CONTACT {
+            sweep.moved = gap;
+            sweep.blockers.clear();
+            sweep.blockers.push(wall);
+        } else if gap <= sweep.moved + CONTACT && gap < amount.abs() {
+            // another wall at the same distance, like the next tile of a flat wall
+            sweep.blockers.push(wall);
+        }
+    }
+
+    sweep
+}
+
+// if the player is only clipping the edge of the walls that stopped it,
+// the direction (along the other axis) to push it around the corner and how far
+fn corner_nudge(position: Vec2, axis: usize, blockers: &[Vec2]) -> Option<(f32, f32)> {
+    let other = 1 - axis;
+    let mut nudge: Option<(f32, f32)> = None;
+
+    for blocker in blockers {
+        let offset = position[other] - blocker[other];
+        let overlap = TOUCHING - offset.abs();
+        if overlap > CORNER_NUDGE || offset == 0.0 {
+            return None;
+        }
+
+        // every wall has to be on the same side, otherwise the player is in a dead end
+        let direction = offset.signum();
+        nudge = match nudge {
+            Some((nudge_direction, _)) if nudge_direction != direction => return None,
+            // far enough to get past every wall
+            Some((_, distance)) => Some((direction, distance.max(overlap))),
+            None => Some((direction, overlap)),
+        };
+    }
+
+    nudge
+}
+
+// where the player ended up after moving
+pub struct Slide {
+    pub position: Vec2,
+    // true if the player was pushed around a corner, the movement that was blocked was used for the push
+    pub nudged: bool,
+}
+
+// move the player by delta, stopping at walls, sliding along them and slipping around corners
+pub fn move_and_slide(position: Vec2, delta: Vec2, walls: &[Vec2]) -> Slide {
+    let mut position = position;
+    let mut nudged = false;
+
+    for axis in 0..2 {
+        let other = 1 - axis;
+        let amount = delta[axis];
+        let swept = sweep(position, axis, amount, walls);
+        position[axis] += swept.moved * amount.signum();
+
+        // only nudge when the player is not already steering on the other axis
+        if swept.blockers.is_empty() || delta[other] != 0.0 { continue; }
+
+        if let Some((direction, overlap)) = corner_nudge(position, axis, &swept.blockers) {
+            // the nudge uses up the movement that was blocked so the player never speeds up
+            let left = amount.abs() - swept.moved;
+            let nudge = sweep(position, other, direction * left.min(overlap), walls);
+            position[other] += nudge.moved * direction;
+            nudged |= nudge.moved > 0.0;
+        }
+    }
+
+    Slide { position, nudged }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const EPSILON: f32 = 0.00001;
+
+    // the player at the origin, about to walk right into walls one tile away
+    fn player_above(overlap: f32) -> Vec2 {
+        Vec2::new(0.0, TOUCHING - overlap)
+    }
+
+    #[test]
+    fn sweep_ends_flush_against_the_wall() {
+        let wall = Vec2::new(TILE_SIZE * 2.0, 0.0);
+        let swept = sweep(Vec2::ZERO, 0, TILE_SIZE * 5.0, &[wall]);
+        assert!((swept.moved - (wall.x - TOUCHING)).abs() < EPSILON);
+        assert_eq!(swept.blockers, vec![wall]);
+
+        let position = move_and_slide(Vec2::ZERO, Vec2::new(TILE_SIZE * 5.0, 0.0), &[wall]).position;
+        assert!((position.x + TOUCHING - wall.x).abs() < EPSILON);
+        assert_eq!(position.y, 0.0);
+    }
+
+    #[test]
+    fn sweep_ignores_walls_behind_and_beside_the_player() {
+        let walls = [
+            Vec2::new(-TILE_SIZE, 0.0),
+            // the player is a bit smaller than a tile so it fits between these
+            Vec2::new(TILE_SIZE, TILE_SIZE),
+            Vec2::new(TILE_SIZE, -TILE_SIZE),
+        ];
+        let swept = sweep(Vec2::ZERO, 0, TILE_SIZE * 3.0, &walls);
+        assert_eq!(swept.moved, TILE_SIZE * 3.0);
+        assert!(swept.blockers.is_empty());
+    }
+
+    #[test]
+    fn player_can_walk_out_of_a_wall_it_is_stuck_in() {
+        let wall = Vec2::new(TILE_SIZE * 0.5, 0.0);
+        let position = move_and_slide(Vec2::ZERO, Vec2::new(-TILE_SIZE, 0.0), &[wall]).position;
+        assert_eq!(position, Vec2::new(-TILE_SIZE, 0.0));
+    }
+
+    #[test]
+    fn player_slides_along_a_wall() {
+        // a flat wall to the right of the player
+        let walls: Vec<Vec2> = (-3..=3).map(|y| Vec2::new(TILE_SIZE, y as f32 * TILE_SIZE)).collect();
+        let delta = Vec2::new(TILE_SIZE * 0.5, TILE_SIZE * 0.5);
+        let position = move_and_slide(Vec2::ZERO, delta, &walls).position;
+        assert!((position.x + TOUCHING - TILE_SIZE).abs() < EPSILON);
+        assert_eq!(position.y, delta.y);
+    }
+
+    #[test]
+    fn player_slips_around_a_corner() {
+        let wall = Vec2::new(TILE_SIZE, 0.0);
+        let start = player_above(TILE_SIZE * 0.3);
+        let slide = move_and_slide(start, Vec2::new(TILE_SIZE * 0.5, 0.0), &[wall]);
+        assert!(slide.nudged);
+        let position = slide.position;
+        // pushed up until it is clear of the wall, and no further
+        assert!((position.y - TOUCHING).abs() < EPSILON);
+        assert!(position.y - start.y <= TILE_SIZE * 0.3 + EPSILON);
+    }
+
+    #[test]
+    fn corner_nudge_only_moves_the_player_by_the_blocked_movement() {
+        let wall = Vec2::new(TILE_SIZE, 0.0);
+        let start = player_above(TILE_SIZE * 0.3);
+        let gap = wall.x - TOUCHING;
+        let position = move_and_slide(start, Vec2::new(gap + TILE_SIZE * 0.1, 0.0), &[wall]).position;
+        assert!((position.y - start.y - TILE_SIZE * 0.1).abs() < EPSILON);
+    }
+
+    #[test]
+    fn player_does_not_slip_around_a_wall_it_is_mostly_in_front_of() {
+        let wall = Vec2::new(TILE_SIZE, 0.0);
+        let start = player_above(TILE_SIZE * 0.5);
+        let slide = move_and_slide(start, Vec2::new(TILE_SIZE * 0.5, 0.0), &[wall]);
+        assert!(!slide.nudged);
+        let position = slide.position;
+        assert_eq!(position.y, start.y);
+        assert!((position.x + TOUCHING - wall.x).abs() < EPSILON);
+    }
+
+    #[test]
+    fn no_corner_nudge_in_a_dead_end() {
+        // two walls each clipping a different edge of the player
+        let position = Vec2::new(TILE_SIZE - TOUCHING, 0.0);
+        let blockers = [Vec2::new(TILE_SIZE, TILE_SIZE * 0.6), Vec2::new(TILE_SIZE, -TILE_SIZE * 0.6)];
+        assert_eq!(corner_nudge(position, 0, &blockers), None);
+        // or a wall straight ahead
+        assert_eq!(corner_nudge(position, 0, &[Vec2::new(TILE_SIZE, 0.0)]), None);
+        // but a single one of them is a corner
+        assert!(corner_nudge(position, 0, &blockers[..1]).is_some());
+    }
+
+    #[test]
+    fn no_corner_nudge_while_steering() {
+        let wall = Vec2::new(TILE_SIZE, 0.0);
+        let start = player_above(TILE_SIZE * 0.3);
+        // already moving down into the wall, so the player is not pushed back up
+        let position = move_and_slide(start, Vec2::new(TILE_SIZE * 0.5, -TILE_SIZE * 0.01), &[wall]).position;
+        assert!(position.y < start.y);
+    }
+}
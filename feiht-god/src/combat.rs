@@ -1,21 +1,133 @@
+use std::collections::VecDeque;
+
 use bevy::prelude::*;
 
-use crate::{GameState, sprites::{SpriteSheet, spawn_sprite}, fadeout::create_fadeout};
+use crate::{TILE_SIZE, GameState, GameRng, player::Player, sprites::{SpriteSheet, spawn_sprite}, fadeout::create_fadeout, stats::{Stats, BaseStats, StatusEffects, Buffs}, leveling::{Experience, GrowthCurves, GrowthCurvesHandle, LevelUp, gain_xp}, damage::calculate_damage, enemies::{EnemyDatabase, EnemyDatabaseHandle}, encounters::Encounter, input::{ActionState, InputAction}, items::{ItemDatabase, ItemDatabaseHandle, Inventory, use_item, inventory_options}, menu::{Menu, MenuOption, spawn_menu}, text::{BitmapText, TextAlign, spawn_text}};
 
 pub struct CombatPlugin;
 
 #[derive(Component)]
-pub struct Enemy;
+pub struct Enemy {
+    // key of the definition in the EnemyDatabase this enemy was spawned from
+    pub id: String,
+}
+
+// an entity with this component takes half damage until its next turn
+#[derive(Component)]
+struct Defending;
+
+// the actions the player can pick from the combat menu, in the order they are drawn
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CombatAction {
+    Attack,
+    Defend,
+    Item,
+    Flee,
+}
+
+impl CombatAction {
+    const ALL: [CombatAction; 4] = [
+        CombatAction::Attack,
+        CombatAction::Defend,
+        CombatAction::Item,
+        CombatAction::Flee,
+    ];
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum BattlePhase {
+    // whoever is at the front of the turn order is acting
+    Turns,
+    // the battle is won and what the player gained is shown until confirm is pressed
+    Summary,
+    // the battle is decided and a fadeout has been created, nothing else should happen
+    Over,
+}
+
+// the queue of entitys waiting to act, the front of the queue is the entity whose turn it is
+pub struct TurnOrder(pub VecDeque<Entity>);
+
+impl TurnOrder {
+    // the fastest fighter acts first, the player wins any ties
+    fn new(player: (Entity, i32), enemys: &[(Entity, i32)]) -> Self {
+        let mut fighters = vec![player];
+        fighters.extend_from_slice(enemys);
+        // sort_by_key is stable so the player stays in front of enemys with the same speed
+        fighters.sort_by_key(|&(_, speed)| std::cmp::Reverse(speed));
+
+        TurnOrder(fighters.into_iter().map(|(entity, _)| entity).collect())
+    }
+}
+
+struct Battle {
+    phase: BattlePhase,
+    // index into CombatAction::ALL of the highlighted menu entry
+    selected: usize,
+    // delay before an enemy acts so the player can follow what is happening
+    enemy_timer: Timer,
+}
+
+// the boxes drawn for each entry of the action menu
+#[derive(Component)]
+struct ActionButton(usize);
+
+// a bar that shows the health of the entity it points to
+#[derive(Component)]
+struct HealthBar {
+    target: Entity,
+    // center of the bar when the target has full health
+    x: f32,
+    width: f32,
+}
+
+// the health of the target written out, next to its health bar
+#[derive(Component)]
+struct HealthText {
+    target: Entity,
+}
+
+// the list of items the player picks from after picking the Item action
+#[derive(Component)]
+struct ItemMenu;
+
+// marker for everything that was spawned for the combat screen
+#[derive(Component)]
+struct CombatUi;
+
+const HEALTH_BAR_WIDTH: f32 = 0.8;
+// the width of the screen the enemys are spread across
+const ENEMY_AREA_WIDTH: f32 = 2.8;
+const BUTTON_WIDTH: f32 = 0.3;
+const BUTTON_HEIGHT: f32 = 0.15;
+// height of the characters on the combat screen
+const TEXT_SIZE: f32 = 0.05;
+const ITEM_TEXT_SIZE: f32 = 0.06;
+// the distance between the tops of two options of a menu
+const ITEM_LINE_HEIGHT: f32 = ITEM_TEXT_SIZE * 1.5;
 
 impl Plugin for CombatPlugin {
     fn build(&self, app: &mut App) {
         app
             .add_system_set(SystemSet::on_update(GameState::Combat)
-                .with_system(test_exit_combat)
                 .with_system(combat_camera)
+                // the item menu is moved through before the player acts
+                .with_system(player_turn.label("player_turn").after("menus"))
+                .with_system(enemy_turn.label("enemy_turn").after("player_turn"))
+                .with_system(check_battle_end.label("battle_end").after("enemy_turn"))
+                .with_system(close_battle_summary.after("battle_end"))
+                .with_system(update_action_buttons.after("player_turn"))
+                .with_system(update_health_bars.after("enemy_turn"))
+                .with_system(update_health_text.after("enemy_turn"))
+            )
+            .add_system_set(SystemSet::on_enter(GameState::Combat)
+                .with_system(spawn_enemy)
+                .with_system(spawn_combat_ui)
             )
-            .add_system_set(SystemSet::on_enter(GameState::Combat).with_system(spawn_enemy))
-            .add_system_set(SystemSet::on_exit(GameState::Combat).with_system(despawn_enemy));
+            .add_system_set(SystemSet::on_exit(GameState::Combat)
+                .with_system(despawn_enemy)
+                .with_system(despawn_combat_ui)
+                .with_system(end_battle)
+            );
     }
 }
 
@@ -30,21 +142,88 @@ fn combat_camera(mut camera_query: Query<&mut Transform, With<Camera>>) {
 
 fn spawn_enemy(
     // commands will be used to spawn a new entity
-    mut commands: Commands, 
+    mut commands: Commands,
+    // the player and its speed are needed to create the turn order
+    player_query: Query<(Entity, &Stats), With<Player>>,
+    // the enemys picked by the encounter that started this fight
+    encounter: Option<Res<Encounter>>,
+    // the enemy definitions decide what gets spawned
+    database_handle: Res<EnemyDatabaseHandle>,
+    databases: Res<Assets<EnemyDatabase>>,
     // we will need the sprite_sheet to pass to spawn_sprite
     sprite_sheet: Res<SpriteSheet>
 ) {
-    let sprite = spawn_sprite(
-        &mut commands, 
-        &sprite_sheet, 
-        127, 
-        Vec3::new(0.0, 0.5, 100.0),
-        10.0
-    );
+    let mut battle = Battle {
+        phase: BattlePhase::Turns,
+        selected: 0,
+        enemy_timer: Timer::from_seconds(0.75, false),
+    };
+
+    // look up the definition of every enemy in the encounter, skipping any that do not exist
+    let database = databases.get(&database_handle.0);
+    let enemy_group: Vec<_> = encounter
+        .iter()
+        .flat_map(|encounter| encounter.enemies.iter())
+        .filter_map(|(id, level)| {
+            let definition = database.and_then(|database| database.enemies.get(id));
+            if definition.is_none() {
+                warn!("Enemy \"{}\" is not defined", id);
+            }
+            definition.map(|definition| (id, *level, definition))
+        })
+        .collect();
+
+    if enemy_group.is_empty() {
+        // without any enemys there is nothing to fight, so go straight back to the overworld
+        error!("No enemys could be spawned for this encounter, leaving combat");
+        battle.phase = BattlePhase::Over;
+        commands.insert_resource(TurnOrder(VecDeque::new()));
+        commands.insert_resource(battle);
+        create_fadeout(&mut commands, GameState::Overworld, &sprite_sheet);
+        return;
+    }
+
+    // split the width of the screen evenly between the enemys
+    let slot_width = ENEMY_AREA_WIDTH / enemy_group.len() as f32;
+    let mut enemys = Vec::new();
+
+    for (i, (id, level, definition)) in enemy_group.into_iter().enumerate() {
+        let x = -ENEMY_AREA_WIDTH / 2.0 + slot_width * (i as f32 + 0.5);
+        // shrink enemys that would not fit in their slot
+        let scale = definition.scale.min(slot_width * 0.9 / TILE_SIZE);
+
+        let sprite = spawn_sprite(
+            &mut commands,
+            &sprite_sheet,
+            definition.sprite_index,
+            Vec3::new(x, 0.5, 100.0),
+            scale
+        );
+
+        let stats = definition.stats.to_stats(level);
+        enemys.push((sprite, stats.speed));
+
+        commands.entity(sprite)
+            .insert(Enemy { id: id.clone() })
+            .insert(stats)
+            .insert(Name::new(definition.name.clone()));
+
+        let bar_width = HEALTH_BAR_WIDTH.min(slot_width * 0.8);
+        let bar = spawn_box(
+            &mut commands,
+            &sprite_sheet,
+            Color::RED,
+            Vec3::new(x, -0.1, 200.0),
+            Vec2::new(bar_width, 0.05)
+        );
+        commands.entity(bar)
+            .insert(HealthBar { target: sprite, x, width: bar_width })
+            .insert(Name::new("Enemy Health"));
+    }
 
-    commands.entity(sprite)
-        .insert(Enemy)
-        .insert(Name::new("Face"));
+    let (player, player_stats) = player_query.single();
+    commands.insert_resource(TurnOrder::new((player, player_stats.speed), &enemys));
+    commands.insert_resource(battle);
 }
 
 fn despawn_enemy(
@@ -61,13 +240,487 @@ fn despawn_enemy(
     }
 }
 
-fn test_exit_combat(
+fn end_battle(mut commands: Commands, mut player_query: Query<&mut Buffs, With<Player>>) {
+    commands.remove_resource::<TurnOrder>();
+    commands.remove_resource::<Battle>();
+    commands.remove_resource::<Encounter>();
+
+    // buffs only last until the end of the battle
+    *player_query.single_mut() = Buffs::default();
+}
+
+// the players turn is done, their statuses (like poison) hurt them and they are sent to the back of the queue
+fn end_player_turn(battle: &mut Battle, turn_order: &mut TurnOrder, stats: &mut Stats, statuses: &StatusEffects) {
+    let damage = statuses.turn_damage(stats);
+    if damage > 0 {
+        stats.damage(damage);
+        info!("Player took {} damage from {:?}", damage, statuses.0);
+    }
+
+    turn_order.0.rotate_left(1);
+    battle.enemy_timer.reset();
+}
+
+// the item menu is drawn over the enemys, on a box that fits every option
+fn spawn_item_menu(commands: &mut Commands, options: Vec<MenuOption>, sprite_sheet: &SpriteSheet) {
+    let lines = options.len() as f32;
+    let padding = 0.05;
+    let width = 1.6;
+    let height = (lines - 1.0) * ITEM_LINE_HEIGHT + ITEM_TEXT_SIZE + padding * 2.0;
+
+    // sprite index 0 is a solid tile, the box is a child of the menu so it goes with it
+    let mut sprite = TextureAtlasSprite::new(0);
+    sprite.color = Color::rgba(0.0, 0.0, 0.0, 0.85);
+    sprite.custom_size = Some(Vec2::new(width, height));
+    let background = commands
+        .spawn_bundle(SpriteSheetBundle {
+            sprite,
+            texture_atlas: sprite_sheet.0.clone(),
+            // the options start at the top left of the menu and go down from there
+            transform: Transform::from_xyz(width / 2.0 - padding, padding - height / 2.0, -1.0),
+            ..Default::default()
+        })
+        .id();
+
+    let menu = spawn_menu(commands, GameState::Combat, options, ITEM_TEXT_SIZE, Vec3::new(-width / 2.0 + padding, 0.6, 300.0));
+    commands.entity(menu)
+        .insert(ItemMenu)
+        .insert(CombatUi)
+        .insert(Name::new("Item Menu"))
+        .add_child(background);
+}
+
+#[allow(clippy::too_many_arguments, clippy::type_complexity)]
+fn player_turn(
+    // commands are used to mark the player as defending and to create the fadeout when fleeing
     mut commands: Commands,
-    mut keyboard: ResMut<Input<KeyCode>>, 
+    // the input is used to move through the menu and to pick an action, it is mutable
+    // so it can be cleared after an action is picked (so it is not picked twice)
+    mut actions: ResMut<ActionState>,
+    mut battle: ResMut<Battle>,
+    mut turn_order: ResMut<TurnOrder>,
+    // every hit rolls for variance and critical hits
+    mut rng: ResMut<GameRng>,
+    // the players stats are needed for attacking, and the rest for using items
+    mut player_query: Query<(Entity, &mut Stats, &mut Inventory, &mut StatusEffects, &mut Buffs), (With<Player>, Without<Enemy>)>,
+    // the enemys are the targets of an attack, Defending is checked to halve the damage
+    mut target_query: Query<(Entity, &mut Stats, Option<&Defending>), With<Enemy>>,
+    // the item menu is open from when the Item action is picked until an item is used or it is cancelled
+    item_menu_query: Query<(Entity, &Menu), With<ItemMenu>>,
+    item_database_handle: Res<ItemDatabaseHandle>,
+    item_databases: Res<Assets<ItemDatabase>>,
     sprite_sheet: Res<SpriteSheet>
 ) {
-    if keyboard.just_pressed(KeyCode::Space) {
-        create_fadeout(&mut commands, GameState::Overworld, &sprite_sheet);
-        keyboard.clear();
+    if battle.phase != BattlePhase::Turns { return; }
+
+    let (player, mut player_stats, mut inventory, mut statuses, mut buffs) = player_query.single_mut();
+    // only run when it is the players turn
+    if turn_order.0.front() != Some(&player) { return; }
+
+    let item_database = item_databases.get(&item_database_handle.0);
+
+    // while the item menu is open it takes the input instead of the action buttons
+    if let Ok((menu_entity, menu)) = item_menu_query.get_single() {
+        // the last option is Back, which is not in the inventory so nothing is used
+        let used = match (menu.picked, item_database) {
+            (Some(index), Some(database)) => use_item(
+                database, index, &mut inventory, &mut player_stats, &mut statuses, Some(&mut *buffs)
+            ),
+            _ => false,
+        };
+        if menu.picked.is_some() || menu.cancelled {
+            commands.entity(menu_entity).despawn_recursive();
+        }
+        if used {
+            end_player_turn(&mut battle, &mut turn_order, &mut player_stats, &statuses);
+        }
+        return;
+    }
+
+    // move the selection through the menu, wrapping around at either end
+    if actions.just_pressed(InputAction::MoveLeft) {
+        battle.selected = (battle.selected + CombatAction::ALL.len() - 1) % CombatAction::ALL.len();
+    }
+    if actions.just_pressed(InputAction::MoveRight) {
+        battle.selected = (battle.selected + 1) % CombatAction::ALL.len();
+    }
+
+    if !actions.just_pressed(InputAction::Confirm) { return; }
+    actions.clear();
+
+    // the player is no longer defending once their turn comes back around
+    commands.entity(player).remove::<Defending>();
+
+    match CombatAction::ALL[battle.selected] {
+        CombatAction::Attack => {
+            // attack the first enemy that is still alive
+            if let Some((enemy, mut enemy_stats, defending)) = target_query
+                .iter_mut()
+                .find(|(_, stats, _)| stats.is_alive())
+            {
+                let hit = calculate_damage(&player_stats, &enemy_stats, 1.0, defending.is_some(), &mut rng.0);
+                enemy_stats.damage(hit.damage);
+                info!("Player hit {:?} for {} damage (critical: {})", enemy, hit.damage, hit.critical);
+            }
+        }
+        CombatAction::Defend => {
+            commands.entity(player).insert(Defending);
+            info!("Player is defending");
+        }
+        CombatAction::Item => {
+            // the turn only ends once an item is picked from the menu
+            let options = inventory_options(item_database, &inventory, &player_stats, &statuses, true);
+            spawn_item_menu(&mut commands, options, &sprite_sheet);
+            return;
+        }
+        CombatAction::Flee => {
+            info!("Player fled");
+            battle.phase = BattlePhase::Over;
+            create_fadeout(&mut commands, GameState::Overworld, &sprite_sheet);
+            return;
+        }
+    }
+
+    end_player_turn(&mut battle, &mut turn_order, &mut player_stats, &statuses);
+}
+
+#[allow(clippy::too_many_arguments, clippy::type_complexity)]
+fn enemy_turn(
+    mut commands: Commands,
+    mut battle: ResMut<Battle>,
+    mut turn_order: ResMut<TurnOrder>,
+    mut rng: ResMut<GameRng>,
+    // the enemys stats are needed to calculate the damage done to the player,
+    // and the enemy id is used to look up which moves it can use
+    enemy_query: Query<(&Enemy, &Stats)>,
+    // some moves also put a status on the player
+    mut player_query: Query<(&mut Stats, &mut StatusEffects, Option<&Defending>), (With<Player>, Without<Enemy>)>,
+    database_handle: Res<EnemyDatabaseHandle>,
+    databases: Res<Assets<EnemyDatabase>>,
+    time: Res<Time>
+) {
+    if battle.phase != BattlePhase::Turns { return; }
+
+    // only run when it is an enemys turn
+    let enemy = match turn_order.0.front() {
+        Some(&entity) => entity,
+        None => return,
+    };
+    let (enemy_info, enemy_stats) = match enemy_query.get(enemy) {
+        Ok(enemy) => enemy,
+        Err(_) => return,
+    };
+
+    // a defeated enemy loses its turn
+    if !enemy_stats.is_alive() {
+        turn_order.0.pop_front();
+        return;
+    }
+
+    // wait a bit before the enemy acts
+    battle.enemy_timer.tick(time.delta());
+    if !battle.enemy_timer.finished() { return; }
+
+    commands.entity(enemy).remove::<Defending>();
+
+    let enemy_move = databases
+        .get(&database_handle.0)
+        .and_then(|database| database.enemies.get(&enemy_info.id))
+        .map(|definition| definition.choose_move(&mut rng.0));
+    let (move_name, power, inflicts) = match enemy_move {
+        Some(enemy_move) => (enemy_move.name, enemy_move.power, enemy_move.inflicts),
+        None => (String::from("Attack"), 1.0, None),
+    };
+
+    let (mut player_stats, mut statuses, defending) = player_query.single_mut();
+    let hit = calculate_damage(enemy_stats, &player_stats, power, defending.is_some(), &mut rng.0);
+    player_stats.damage(hit.damage);
+    info!(
+        "{} used {} on the player for {} damage (critical: {})",
+        enemy_info.id, move_name, hit.damage, hit.critical
+    );
+    if let Some(status) = inflicts {
+        statuses.add(status);
+        info!("The player is now {:?}", status);
+    }
+
+    // the enemys turn is done, send it to the back of the queue
+    turn_order.0.rotate_left(1);
+    battle.enemy_timer.reset();
+}
+
+// decide if the battle has been won or lost, a lost battle fades out to the game over
+// screen and a won battle shows the rewards first (see close_battle_summary)
+#[allow(clippy::too_many_arguments, clippy::type_complexity)]
+fn check_battle_end(
+    mut commands: Commands,
+    mut battle: ResMut<Battle>,
+    mut rng: ResMut<GameRng>,
+    // the player levels up from the xp of the enemys, and their loot goes into the players inventory
+    mut player_query: Query<(&mut Stats, &mut BaseStats, &mut Experience, &mut Inventory), (With<Player>, Without<Enemy>)>,
+    enemy_query: Query<(&Enemy, &Stats)>,
+    database_handle: Res<EnemyDatabaseHandle>,
+    databases: Res<Assets<EnemyDatabase>>,
+    item_database_handle: Res<ItemDatabaseHandle>,
+    item_databases: Res<Assets<ItemDatabase>>,
+    growth_handle: Res<GrowthCurvesHandle>,
+    growth_curves: Res<Assets<GrowthCurves>>,
+    sprite_sheet: Res<SpriteSheet>
+) {
+    if battle.phase != BattlePhase::Turns { return; }
+
+    // with no enemys every enemy would count as defeated
+    if enemy_query.is_empty() { return; }
+
+    let (mut player_stats, mut base_stats, mut experience, mut inventory) = player_query.single_mut();
+    if !player_stats.is_alive() {
+        info!("Player was defeated");
+        battle.phase = BattlePhase::Over;
+        create_fadeout(&mut commands, GameState::GameOver, &sprite_sheet);
+    } else if enemy_query.iter().all(|(_, stats)| !stats.is_alive()) {
+        info!("Player won the battle");
+
+        // add up the rewards of every defeated enemy
+        let mut xp = 0;
+        let mut loot = Vec::new();
+        if let Some(database) = databases.get(&database_handle.0) {
+            for (enemy, _) in enemy_query.iter() {
+                if let Some(definition) = database.enemies.get(&enemy.id) {
+                    xp += definition.xp_reward;
+                    loot.extend(definition.roll_loot(&mut rng.0));
+                }
+            }
+        }
+        info!("Gained {} xp and found {:?}", xp, loot);
+
+        let level_ups = match growth_curves.get(&growth_handle.0) {
+            Some(curves) => gain_xp(curves, xp, &mut experience, &mut player_stats, &mut base_stats),
+            None => {
+                experience.0 += xp;
+                Vec::new()
+            }
+        };
+
+        // the names of the items that were found, for the summary
+        let item_database = item_databases.get(&item_database_handle.0);
+        let mut found = Vec::new();
+        for item in loot {
+            match item_database.and_then(|database| database.items.get(&item)) {
+                Some(definition) => {
+                    if inventory.add(&item, 1, definition.max_stack) > 0 {
+                        info!("Can not carry any more {}", definition.name);
+                    }
+                    found.push(definition.name.clone());
+                }
+                None => warn!("Item \"{}\" is not defined", item),
+            }
+        }
+
+        battle.phase = BattlePhase::Summary;
+        spawn_battle_summary(&mut commands, xp, &found, &level_ups, &sprite_sheet);
+    }
+}
+
+// a box over the enemys listing the xp, the items and every level the player gained
+fn spawn_battle_summary(commands: &mut Commands, xp: u32, found: &[String], level_ups: &[LevelUp], sprite_sheet: &SpriteSheet) {
+    let mut lines = vec![
+        (String::from("Victory!"), Color::WHITE),
+        (format!("Gained {} xp", xp), Color::WHITE),
+    ];
+    for name in found {
+        lines.push((format!("Found {}", name), Color::WHITE));
+    }
+    for level_up in level_ups {
+        lines.push((format!("Reached level {}!", level_up.level), Color::YELLOW));
+        lines.push((
+            format!(
+                "HP +{}  Atk +{}  Def +{}  Spd +{}",
+                level_up.max_health, level_up.attack, level_up.defense, level_up.speed
+            ),
+            Color::YELLOW
+        ));
+    }
+
+    let line_height = TEXT_SIZE * 1.6;
+    let height = lines.len() as f32 * line_height + 0.1;
+    // the box is centered a little above the players health bar and grows upwards
+    let bottom = -0.3;
+    let top = bottom + height;
+    let summary_box = spawn_box(
+        commands,
+        sprite_sheet,
+        Color::rgba(0.0, 0.0, 0.0, 0.85),
+        Vec3::new(0.0, (top + bottom) / 2.0, 300.0),
+        Vec2::new(1.8, height)
+    );
+    commands.entity(summary_box).insert(Name::new("Battle Summary"));
+
+    for (i, (line, color)) in lines.iter().enumerate() {
+        spawn_combat_text(
+            commands,
+            line,
+            *color,
+            TextAlign::Center,
+            Vec3::new(0.0, top - 0.05 - i as f32 * line_height, 301.0)
+        );
+    }
+}
+
+// the summary stays up until confirm is pressed, then the player goes back to the overworld
+fn close_battle_summary(
+    mut commands: Commands,
+    mut actions: ResMut<ActionState>,
+    mut battle: ResMut<Battle>,
+    sprite_sheet: Res<SpriteSheet>
+) {
+    if battle.phase != BattlePhase::Summary || !actions.just_pressed(InputAction::Confirm) { return; }
+    actions.clear();
+
+    battle.phase = BattlePhase::Over;
+    create_fadeout(&mut commands, GameState::Overworld, &sprite_sheet);
+}
+
+// text on the combat screen, drawn above the boxes
+fn spawn_combat_text(commands: &mut Commands, text: &str, color: Color, align: TextAlign, translation: Vec3) -> Entity {
+    let text = spawn_text(
+        commands,
+        BitmapText {
+            text: text.to_string(),
+            size: TEXT_SIZE,
+            color,
+            align,
+            max_width: None,
+        },
+        translation
+    );
+    commands.entity(text).insert(CombatUi);
+    text
+}
+
+// sprite index 0 is a solid tile, so it can be tinted and stretched into a box
+fn spawn_box(
+    commands: &mut Commands,
+    sprite_sheet: &SpriteSheet,
+    color: Color,
+    translation: Vec3,
+    size: Vec2
+) -> Entity {
+    let mut sprite = TextureAtlasSprite::new(0);
+    sprite.color = color;
+    sprite.custom_size = Some(size);
+
+    commands
+        .spawn_bundle(SpriteSheetBundle {
+            sprite,
+            texture_atlas: sprite_sheet.0.clone(),
+            transform: Transform {
+                translation,
+                ..Default::default()
+            },
+            ..Default::default()
+        })
+        .insert(CombatUi)
+        .id()
+}
+
+fn spawn_combat_ui(
+    mut commands: Commands,
+    player_query: Query<(Entity, &Name), With<Player>>,
+    sprite_sheet: Res<SpriteSheet>
+) {
+    // one box for every action, spread evenly along the bottom of the screen
+    for (i, action) in CombatAction::ALL.iter().enumerate() {
+        let x = (i as f32 - 1.5) * (BUTTON_WIDTH + 0.1);
+        let button = spawn_box(
+            &mut commands,
+            &sprite_sheet,
+            Color::GRAY,
+            Vec3::new(x, -0.75, 200.0),
+            Vec2::new(BUTTON_WIDTH, BUTTON_HEIGHT)
+        );
+        commands.entity(button)
+            .insert(ActionButton(i))
+            .insert(Name::new(format!("{:?} Button", action)));
+
+        // the label is not a child of the button so it is not scaled with it, the text
+        // is placed from the top of the first line so it is moved up half a line to center it
+        spawn_combat_text(
+            &mut commands,
+            &format!("{:?}", action),
+            Color::BLACK,
+            TextAlign::Center,
+            Vec3::new(x, -0.75 + TEXT_SIZE / 2.0, 201.0)
+        );
+    }
+
+    // the players health bar, the enemy health bars are spawned with the enemys
+    let (player, name) = player_query.single();
+    let bar = spawn_box(
+        &mut commands,
+        &sprite_sheet,
+        Color::GREEN,
+        Vec3::new(0.0, -0.45, 200.0),
+        Vec2::new(HEALTH_BAR_WIDTH, 0.05)
+    );
+    commands.entity(bar)
+        .insert(HealthBar { target: player, x: 0.0, width: HEALTH_BAR_WIDTH })
+        .insert(Name::new("Player Health"));
+
+    // the name of the player under the left end of the bar and the health under the right end
+    let left = -HEALTH_BAR_WIDTH / 2.0;
+    let right = HEALTH_BAR_WIDTH / 2.0;
+    spawn_combat_text(&mut commands, name.as_str(), Color::WHITE, TextAlign::Left, Vec3::new(left, -0.5, 201.0));
+    let text = spawn_combat_text(&mut commands, "", Color::WHITE, TextAlign::Right, Vec3::new(right, -0.5, 201.0));
+    commands.entity(text).insert(HealthText { target: player });
+}
+
+fn update_health_text(
+    mut text_query: Query<(&HealthText, &mut BitmapText)>,
+    stats_query: Query<&Stats>
+) {
+    for (health_text, mut text) in text_query.iter_mut() {
+        if let Ok(stats) = stats_query.get(health_text.target) {
+            let health = format!("{}/{}", stats.health.max(0), stats.max_health);
+            // only change the text when the health changes so it is not laid out again every frame
+            if text.text != health {
+                text.text = health;
+            }
+        }
+    }
+}
+
+fn despawn_combat_ui(mut commands: Commands, ui_query: Query<Entity, With<CombatUi>>) {
+    for entity in ui_query.iter() {
+        commands.entity(entity).despawn_recursive();
+    }
+}
+
+// highlight the action that is currently selected
+fn update_action_buttons(
+    battle: Res<Battle>,
+    mut button_query: Query<(&ActionButton, &mut TextureAtlasSprite)>
+) {
+    for (button, mut sprite) in button_query.iter_mut() {
+        sprite.color = if button.0 == battle.selected {
+            Color::WHITE
+        } else {
+            Color::GRAY
+        };
+    }
+}
+
+fn update_health_bars(
+    // the bars are stretched from the left edge to match the health of their target
+    mut bar_query: Query<(&HealthBar, &mut TextureAtlasSprite, &mut Transform)>,
+    stats_query: Query<&Stats>
+) {
+    for (bar, mut sprite, mut transform) in bar_query.iter_mut() {
+        if let Ok(stats) = stats_query.get(bar.target) {
+            let percent = stats.health.max(0) as f32 / stats.max_health as f32;
+            let width = bar.width * percent;
+            sprite.custom_size = Some(Vec2::new(width, 0.05));
+            // keep the left edge in place while the bar shrinks
+            transform.translation.x = bar.x + (width - bar.width) / 2.0;
+        }
     }
 }
@@ -0,0 +1,168 @@
+// Damage calculation for combat, nothing in here touches the ECS so every
+// formula can be called directly with hand written stats and a seeded rng
+
+use rand::Rng;
+
+use crate::stats::Stats;
+
+// chance of any single hit being a critical hit
+pub const CRITICAL_CHANCE: f64 = 1.0 / 16.0;
+// critical hits are multiplied by this and ignore the defenders defense
+pub const CRITICAL_MULTIPLIER: f32 = 1.5;
+// damage is randomly scaled by up to this much in either direction (0.1 is +/- 10%)
+pub const DAMAGE_VARIANCE: f32 = 0.1;
+// a defending target takes this fraction of the damage
+pub const DEFEND_MULTIPLIER: f32 = 0.5;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Hit {
+    pub damage: i32,
+    pub critical: bool,
+}
+
+// how much of an attack gets through the defense, attack * attack / (attack + defense)
+// keeps damage above 0 and makes each point of defense worth less as attack grows
+pub fn mitigate(attack: f32, defense: f32) -> f32 {
+    if attack <= 0.0 {
+        return 0.0;
+    }
+    attack * attack / (attack + defense.max(0.0))
+}
+
+// scale the damage by the variance roll, the roll should be in the range -1.0..=1.0
+pub fn apply_variance(damage: f32, roll: f32) -> f32 {
+    damage * (1.0 + DAMAGE_VARIANCE * roll.clamp(-1.0, 1.0))
+}
+
+// the full formula once all the random parts have been decided, this is where
+// the numbers should be balanced since the output only depends on the input
+pub fn resolve_hit(
+    attacker: &Stats,
+    defender: &Stats,
+    // multiplier of the move being used, a normal attack is 1.0
+    power: f32,
+    defending: bool,
+    critical: bool,
+    variance_roll: f32
+) -> Hit {
+    let attack = attacker.attack as f32 * power;
+    let mut damage = if critical {
+        attack * CRITICAL_MULTIPLIER
+    } else {
+        mitigate(attack, defender.defense as f32)
+    };
+
+    damage = apply_variance(damage, variance_roll);
+
+    if defending {
+        damage *= DEFEND_MULTIPLIER;
+    }
+
+    Hit {
+        // every hit does at least 1 damage
+        damage: (damage.round() as i32).max(1),
+        critical,
+    }
+}
+
+// roll for a critical hit and the variance then resolve the hit
+pub fn calculate_damage<R: Rng + ?Sized>(
+    attacker: &Stats,
+    defender: &Stats,
+    power: f32,
+    defending: bool,
+    rng: &mut R
+) -> Hit {
+    let critical = rng.gen_bool(CRITICAL_CHANCE);
+    let variance_roll = rng.gen_range(-1.0..=1.0);
+
+    resolve_hit(attacker, defender, power, defending, critical, variance_roll)
+}
+
+#[cfg(test)]
+mod tests {
+    use rand::{rngs::StdRng, SeedableRng};
+
+    use super::*;
+    use crate::{enemies::EnemyDatabase, player::starting_stats};
+
+    fn fighter(attack: i32, defense: i32) -> Stats {
+        Stats { health: 10, max_health: 10, attack, defense, speed: 1, level: 1 }
+    }
+
+    // the stats of an enemy as it is written in the enemy file
+    fn enemy_stats(id: &str) -> Stats {
+        let database: EnemyDatabase = ron::de::from_str(include_str!("../assets/data/monsters.enemies.ron")).unwrap();
+        database.enemies[id].stats.to_stats(1)
+    }
+
+    #[test]
+    fn mitigate_stays_between_0_and_the_attack() {
+        assert_eq!(mitigate(0.0, 5.0), 0.0);
+        assert_eq!(mitigate(-3.0, 5.0), 0.0);
+        // no defense lets the whole attack through, negative defense counts as none
+        assert_eq!(mitigate(8.0, 0.0), 8.0);
+        assert_eq!(mitigate(8.0, -4.0), 8.0);
+        assert_eq!(mitigate(8.0, 8.0), 4.0);
+        for defense in 0..100 {
+            let damage = mitigate(10.0, defense as f32);
+            assert!(damage > 0.0 && damage <= 10.0, "{} damage through {} defense", damage, defense);
+        }
+    }
+
+    #[test]
+    fn variance_is_clamped_to_its_range() {
+        assert_eq!(apply_variance(10.0, 0.0), 10.0);
+        assert!((apply_variance(10.0, 1.0) - 10.0 * (1.0 + DAMAGE_VARIANCE)).abs() < 1e-5);
+        assert!((apply_variance(10.0, -1.0) - 10.0 * (1.0 - DAMAGE_VARIANCE)).abs() < 1e-5);
+        // rolls out of range are treated like the ends of the range
+        assert_eq!(apply_variance(10.0, 5.0), apply_variance(10.0, 1.0));
+        assert_eq!(apply_variance(10.0, -5.0), apply_variance(10.0, -1.0));
+    }
+
+    #[test]
+    fn resolve_hit_does_at_least_1_damage() {
+        let hit = resolve_hit(&fighter(1, 0), &fighter(1, 999), 1.0, true, false, -1.0);
+        assert_eq!(hit, Hit { damage: 1, critical: false });
+        let hit = resolve_hit(&fighter(0, 0), &fighter(1, 0), 1.0, false, false, 0.0);
+        assert_eq!(hit.damage, 1);
+    }
+
+    #[test]
+    fn resolve_hit_applies_power_and_defending() {
+        let attacker = fighter(10, 0);
+        let defender = fighter(1, 0);
+        assert_eq!(resolve_hit(&attacker, &defender, 1.0, false, false, 0.0).damage, 10);
+        assert_eq!(resolve_hit(&attacker, &defender, 1.5, false, false, 0.0).damage, 15);
+        assert_eq!(resolve_hit(&attacker, &defender, 1.0, true, false, 0.0).damage, 5);
+    }
+
+    #[test]
+    fn critical_hits_are_multiplied_and_ignore_defense() {
+        let attacker = fighter(10, 0);
+        let defender = fighter(1, 10);
+        let normal = resolve_hit(&attacker, &defender, 1.0, false, false, 0.0);
+        let critical = resolve_hit(&attacker, &defender, 1.0, false, true, 0.0);
+        assert_eq!(normal.damage, 5);
+        assert_eq!(critical, Hit { damage: (10.0 * CRITICAL_MULTIPLIER).round() as i32, critical: true });
+    }
+
+    // 8 hits in a row from the attacker with the rng seeded the same way every time
+    fn seeded_hits(attacker: &Stats, defender: &Stats, seed: u64) -> Vec<i32> {
+        let mut rng = StdRng::seed_from_u64(seed);
+        (0..8)
+            .map(|_| calculate_damage(attacker, defender, 1.0, false, &mut rng).damage)
+            .collect()
+    }
+
+    #[test]
+    fn seeded_damage_is_deterministic() {
+        let player = starting_stats();
+        let face = enemy_stats("face");
+        assert_eq!(seeded_hits(&player, &face, 42), seeded_hits(&player, &face, 42));
+        assert_eq!(seeded_hits(&face, &player, 7), seeded_hits(&face, &player, 7));
+
+        // the numbers to change when balancing, the 13 is a critical hit
+        assert_eq!(seeded_hits(&player, &face, 42), vec![7, 7, 8, 7, 7, 7, 13, 7]);
+    }
+}
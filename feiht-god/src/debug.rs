@@ -1,7 +1,7 @@
 use bevy::prelude::*;
 use bevy_inspector_egui::{WorldInspectorPlugin, RegisterInspectable};
 
-use crate::{player::{Player, EncounterTracker}, sprites::{Facing, AnimationTimer}};
+use crate::{animation::AnimationEvent, player::{Player, Velocity, EncounterTracker}, sprites::Facing, stats::Stats, tilemap::{SpawnPoint, Warp, TileProperties}};
 
 pub struct DebugPlugin;
 
@@ -10,9 +10,24 @@ impl Plugin for DebugPlugin {
         if cfg!(debug_assertions) {
             app.add_plugin(WorldInspectorPlugin::new())
                 .register_type::<EncounterTracker>()
-                .register_type::<AnimationTimer>()
                 .register_inspectable::<Player>()
-                .register_inspectable::<Facing>();
+                .register_inspectable::<Velocity>()
+                .register_inspectable::<Facing>()
+                .register_inspectable::<Stats>()
+                .register_inspectable::<SpawnPoint>()
+                .register_inspectable::<Warp>()
+                .register_inspectable::<TileProperties>()
+                .add_system(log_animation_events);
         }
     }
-}
\ No newline at end of file
+}
+
+// print every animation event so the events in the .anim.ron files can be checked
+fn log_animation_events(mut events: EventReader<AnimationEvent>, name_query: Query<&Name>) {
+    for event in events.iter() {
+        match name_query.get(event.entity) {
+            Ok(name) => debug!("Animation event \"{}\" on {}", event.name, name),
+            Err(_) => debug!("Animation event \"{}\" on {:?}", event.name, event.entity),
+        }
+    }
+}
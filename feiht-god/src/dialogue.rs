@@ -0,0 +1,430 @@
+// Conversations with npcs. A conversation is read from a .dialogue.ron file (see
+// assets/dialogue/old_man.dialogue.ron) made of named nodes, every node is one line of text
+// that either goes on to another node or lets the player pick from some choices. Nodes can
+// set and clear world flags and where the conversation goes can depend on them, so an npc
+// can say something else the second time the player talks to them.
+
+use bevy::{prelude::*, asset::{AssetLoader, LoadContext, LoadedAsset, BoxedFuture}, reflect::TypeUuid, utils::{HashMap, HashSet}};
+use serde::Deserialize;
+
+use crate::{TILE_SIZE, GameState, player::Player, sprites::{Facing, SpriteSheet}, tilemap::{Npc, TileGrid}, input::{ActionState, InputAction}, main_menu::NewGame, text::{BitmapText, TextAlign, TextReveal, spawn_text}};
+
+pub struct DialoguePlugin;
+
+// a check against the world flags
+#[derive(Deserialize, Clone, Debug)]
+pub enum FlagCondition {
+    Set(String),
+    NotSet(String),
+}
+
+// go to a node if the condition holds (or there is no condition)
+#[derive(Deserialize, Clone, Debug)]
+#[serde(deny_unknown_fields)]
+pub struct Jump {
+    #[serde(default)]
+    pub condition: Option<FlagCondition>,
+    pub node: String,
+}
+
+#[derive(Deserialize, Clone, Debug)]
+#[serde(deny_unknown_fields)]
+pub struct DialogueChoice {
+    pub text: String,
+    // the choice is only shown if this holds
+    #[serde(default)]
+    pub condition: Option<FlagCondition>,
+    // the node picking this choice goes to, the conversation ends when left out
+    #[serde(default)]
+    pub next: Option<String>,
+}
+
+#[derive(Deserialize, Clone, Debug)]
+#[serde(deny_unknown_fields)]
+pub struct DialogueNode {
+    // the name shown above the line, left out for things like signs
+    #[serde(default)]
+    pub speaker: Option<String>,
+    pub text: String,
+    // flags changed when the node is shown
+    #[serde(default)]
+    pub set_flags: Vec<String>,
+    #[serde(default)]
+    pub clear_flags: Vec<String>,
+    // the player picks one of these once the line is shown
+    #[serde(default)]
+    pub choices: Vec<DialogueChoice>,
+    // without choices (or when none of them can be shown) the conversation goes to the
+    // first of these whose condition holds, and ends if there is none
+    #[serde(default)]
+    pub next: Vec<Jump>,
+}
+
+#[derive(Deserialize, TypeUuid, Clone, Debug)]
+#[uuid = "7f35cbef-8bdb-4974-b651-434ae5cfe0f5"]
+#[serde(deny_unknown_fields)]
+pub struct DialogueAsset {
+    // the conversation starts at the first of these whose condition holds
+    pub start: Vec<Jump>,
+    pub nodes: HashMap<String, DialogueNode>,
+}
+
+impl DialogueAsset {
+    // every jump and choice has to go to a node that exists
+    fn validate(&self) -> Result<(), String> {
+        let check = |from: &str, to: &str| match self.nodes.contains_key(to) {
+            true => Ok(()),
+            false => Err(format!("{} goes to \"{}\" which is not a node", from, to)),
+        };
+
+        for jump in self.start.iter() {
+            check("the start", &jump.node)?;
+        }
+        for (name, node) in self.nodes.iter() {
+            let from = format!("node \"{}\"", name);
+            for jump in node.next.iter() {
+                check(&from, &jump.node)?;
+            }
+            for choice in node.choices.iter() {
+                if let Some(next) = &choice.next {
+                    check(&from, next)?;
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
+#[derive(Default)]
+struct DialogueLoader;
+
+impl AssetLoader for DialogueLoader {
+    fn load<'a>(
+        &'a self,
+        bytes: &'a [u8],
+        load_context: &'a mut LoadContext,
+    ) -> BoxedFuture<'a, Result<(), anyhow::Error>> {
+        Box::pin(async move {
+            let dialogue: DialogueAsset = ron::de::from_bytes(bytes)?;
+            dialogue.validate().map_err(|error| anyhow::anyhow!("{}, {}", load_context.path().display(), error))?;
+            load_context.set_default_asset(LoadedAsset::new(dialogue));
+            Ok(())
+        })
+    }
+
+    fn extensions(&self) -> &[&str] {
+        &["dialogue.ron"]
+    }
+}
+
+// the flags set by conversations, a flag is set if it is in here
+#[derive(Default)]
+pub struct WorldFlags(pub HashSet<String>);
+
+impl WorldFlags {
+    pub fn is_set(&self, flag: &str) -> bool {
+        self.0.contains(flag)
+    }
+
+    // true when there is no condition
+    pub fn check(&self, condition: Option<&FlagCondition>) -> bool {
+        match condition {
+            Some(FlagCondition::Set(flag)) => self.is_set(flag),
+            Some(FlagCondition::NotSet(flag)) => !self.is_set(flag),
+            None => true,
+        }
+    }
+
+    // the node of the first jump whose condition holds
+    fn jump<'a>(&self, jumps: &'a [Jump]) -> Option<&'a str> {
+        jumps
+            .iter()
+            .find(|jump| self.check(jump.condition.as_ref()))
+            .map(|jump| jump.node.as_str())
+    }
+}
+
+// the conversation that is going on, the box is a child of the camera so it stays on screen
+#[derive(Component)]
+struct DialogueBox {
+    dialogue: Handle<DialogueAsset>,
+    node: String,
+    // the choices of the node the flags allow, as indexes into the choices of the node
+    choices: Vec<usize>,
+    selected: usize,
+    // the texts of the box
+    speaker: Entity,
+    line: Entity,
+    choice_list: Entity,
+}
+
+// where the box is in front of the camera, in front of the map but behind the map banner and the fadeout
+const BOX_Z: f32 = -2.0;
+const BOX_WIDTH: f32 = 2.8;
+const BOX_HEIGHT: f32 = 0.55;
+const BOX_Y: f32 = -0.7;
+// the left edge of the text in the box
+const TEXT_LEFT: f32 = -1.3;
+const TEXT_SIZE: f32 = 0.07;
+const CHARACTERS_PER_SECOND: f32 = 40.0;
+
+impl Plugin for DialoguePlugin {
+    fn build(&self, app: &mut App) {
+        app
+            .add_asset::<DialogueAsset>()
+            .init_asset_loader::<DialogueLoader>()
+            .init_resource::<WorldFlags>()
+            .add_system_set(SystemSet::on_update(GameState::Overworld)
+                .with_system(start_dialogue.label("start_dialogue"))
+                .with_system(advance_dialogue.after("start_dialogue"))
+            )
+            .add_system_set(SystemSet::on_exit(GameState::Overworld).with_system(end_dialogue))
+            .add_system(reset_flags);
+    }
+}
+
+// a new game starts with no flags set
+fn reset_flags(mut new_game_events: EventReader<NewGame>, mut flags: ResMut<WorldFlags>) {
+    if new_game_events.iter().count() > 0 {
+        *flags = WorldFlags::default();
+    }
+}
+
+// a conversation can only be left by quitting to the title screen, the box is not left on it
+fn end_dialogue(mut commands: Commands, box_query: Query<Entity, With<DialogueBox>>) {
+    for entity in box_query.iter() {
+        commands.entity(entity).despawn_recursive();
+    }
+}
+
+// show a node, changing the flags it changes, returns the choices of the node the player can pick
+fn enter_node(node: &DialogueNode, flags: &mut WorldFlags) -> Vec<usize> {
+    for flag in node.set_flags.iter() {
+        flags.0.insert(flag.clone());
+    }
+    for flag in node.clear_flags.iter() {
+        flags.0.remove(flag);
+    }
+
+    node.choices
+        .iter()
+        .enumerate()
+        .filter(|(_, choice)| flags.check(choice.condition.as_ref()))
+        .map(|(index, _)| index)
+        .collect()
+}
+
+// one choice on every line with an arrow next to the selected one
+fn choice_list(node: &DialogueNode, choices: &[usize], selected: usize) -> String {
+    choices
+        .iter()
+        .enumerate()
+        .map(|(i, &choice)| format!("{} {}", if i == selected { ">" } else { " " }, node.choices[choice].text))
+        .collect::<Vec<_>>()
+        .join("\n")
+}
+
+#[allow(clippy::too_many_arguments)]
+fn start_dialogue(
+    // commands are used to spawn the dialogue box
+    mut commands: Commands,
+    // the player is stopped while talking, and talks to the npc they are facing
+    mut player_query: Query<(&mut Player, &Transform, &Facing)>,
+    npc_query: Query<(&Transform, &Npc), Without<Player>>,
+    // only the npcs in the cells around the player are checked
+    grid: Res<TileGrid>,
+    dialogues: Res<Assets<DialogueAsset>>,
+    mut flags: ResMut<WorldFlags>,
+    // the confirm press is cleared so it does not also skip the first line
+    mut actions: ResMut<ActionState>,
+    // the box is put in front of the camera
+    camera_query: Query<Entity, With<Camera>>,
+    sprites: Res<SpriteSheet>
+) {
+    let (mut player, transform, facing) = player_query.single_mut();
+    if !player.is_active || !actions.just_pressed(InputAction::Confirm) { return; }
+
+    // the middle of the tile in front of the player
+    let target = transform.translation.truncate() + facing.direction() * TILE_SIZE;
+    let npc = grid
+        .around(transform.translation)
+        .filter_map(|entity| npc_query.get(entity).ok())
+        .find(|(npc_transform, _)| (npc_transform.translation.truncate() - target).abs().max_element() < TILE_SIZE / 2.0);
+
+    // npcs with no dialogue (or dialogue that is still loading) have nothing to say
+    let (handle, dialogue) = match npc
+        .and_then(|(_, npc)| npc.dialogue.as_ref())
+        .and_then(|handle| dialogues.get(handle).map(|dialogue| (handle, dialogue)))
+    {
+        Some(dialogue) => dialogue,
+        None => return,
+    };
+    let (name, node) = match flags.jump(&dialogue.start) {
+        Some(name) => (name, &dialogue.nodes[name]),
+        None => return,
+    };
+    let camera = match camera_query.iter().next() {
+        Some(camera) => camera,
+        None => return,
+    };
+
+    actions.clear();
+    player.is_active = false;
+    let choices = enter_node(node, &mut flags);
+
+    let text = |text: &str, color: Color, max_width: Option<f32>| BitmapText {
+        text: text.to_string(),
+        size: TEXT_SIZE,
+        color,
+        align: TextAlign::Left,
+        max_width,
+    };
+    let speaker = spawn_text(
+        &mut commands,
+        text(node.speaker.as_deref().unwrap_or_default(), Color::YELLOW, None),
+        Vec3::new(TEXT_LEFT, BOX_Y + BOX_HEIGHT / 2.0 - 0.03, 0.1)
+    );
+    let line = spawn_text(
+        &mut commands,
+        text(&node.text, Color::WHITE, Some(-2.0 * TEXT_LEFT)),
+        Vec3::new(TEXT_LEFT, BOX_Y + BOX_HEIGHT / 2.0 - 0.11, 0.1)
+    );
+    commands.entity(line).insert(TextReveal::new(CHARACTERS_PER_SECOND));
+    // the choices are filled in once the line has been shown
+    let choice_list = spawn_text(
+        &mut commands,
+        text("", Color::WHITE, None),
+        Vec3::new(TEXT_LEFT + 0.1, BOX_Y - 0.02, 0.1)
+    );
+
+    // sprite index 0 is a solid tile, so it can be tinted and stretched into a box
+    let mut sprite = TextureAtlasSprite::new(0);
+    sprite.color = Color::rgba(0.05, 0.05, 0.1, 0.9);
+    sprite.custom_size = Some(Vec2::new(BOX_WIDTH, BOX_HEIGHT));
+    let background = commands
+        .spawn_bundle(SpriteSheetBundle {
+            sprite,
+            texture_atlas: sprites.0.clone(),
+            transform: Transform::from_xyz(0.0, BOX_Y, 0.0),
+            ..Default::default()
+        })
+        .id();
+
+    let dialogue_box = commands
+        .spawn()
+        .insert(DialogueBox {
+            dialogue: handle.clone(),
+            node: name.to_string(),
+            choices,
+            selected: 0,
+            speaker,
+            line,
+            choice_list,
+        })
+        .insert(Name::new("Dialogue Box"))
+        .insert(Transform::from_xyz(0.0, 0.0, BOX_Z))
+        .insert(GlobalTransform::default())
+        .push_children(&[background, speaker, line, choice_list])
+        .id();
+    commands.entity(camera).add_child(dialogue_box);
+}
+
+fn advance_dialogue(
+    // commands are used to despawn the box once the conversation is over
+    mut commands: Commands,
+    mut box_query: Query<(Entity, &mut DialogueBox)>,
+    // the texts of the box
+    mut text_query: Query<(&mut BitmapText, Option<&mut TextReveal>)>,
+    // the player can move again once the conversation is over
+    mut player_query: Query<&mut Player>,
+    dialogues: Res<Assets<DialogueAsset>>,
+    mut flags: ResMut<WorldFlags>,
+    mut actions: ResMut<ActionState>
+) {
+    let (entity, mut dialogue_box) = match box_query.get_single_mut() {
+        Ok(dialogue_box) => dialogue_box,
+        Err(_) => return,
+    };
+
+    // a node the dialogue file does not have (or a dialogue that was unloaded) ends the dialogue
+    let node = match dialogues
+        .get(&dialogue_box.dialogue)
+        .and_then(|dialogue| dialogue.nodes.get(&dialogue_box.node))
+    {
+        Some(node) => node,
+        None => {
+            commands.entity(entity).despawn_recursive();
+            player_query.single_mut().is_active = true;
+            return;
+        }
+    };
+
+    let finished = match text_query.get_mut(dialogue_box.line) {
+        Ok((text, Some(mut reveal))) => {
+            // confirm while the line is still being shown shows all of it
+            if !reveal.is_finished(&text) && actions.just_pressed(InputAction::Confirm) {
+                reveal.finish();
+                actions.clear();
+                return;
+            }
+            reveal.is_finished(&text)
+        }
+        _ => true,
+    };
+    if !finished { return; }
+
+    let choice_count = dialogue_box.choices.len();
+    if choice_count > 0 {
+        if actions.just_pressed(InputAction::MoveDown) {
+            dialogue_box.selected = (dialogue_box.selected + 1) % choice_count;
+        }
+        if actions.just_pressed(InputAction::MoveUp) {
+            dialogue_box.selected = (dialogue_box.selected + choice_count - 1) % choice_count;
+        }
+    }
+
+    if actions.just_pressed(InputAction::Confirm) {
+        actions.clear();
+
+        let next = match dialogue_box.choices.get(dialogue_box.selected) {
+            Some(&choice) => node.choices[choice].next.as_deref(),
+            None => flags.jump(&node.next),
+        };
+        let next = match next.and_then(|name| dialogues.get(&dialogue_box.dialogue)?.nodes.get_key_value(name)) {
+            Some(next) => next,
+            None => {
+                // the conversation is over
+                commands.entity(entity).despawn_recursive();
+                player_query.single_mut().is_active = true;
+                return;
+            }
+        };
+
+        let (name, node) = next;
+        dialogue_box.node = name.clone();
+        dialogue_box.choices = enter_node(node, &mut flags);
+        dialogue_box.selected = 0;
+
+        if let Ok((mut text, _)) = text_query.get_mut(dialogue_box.speaker) {
+            text.text = node.speaker.clone().unwrap_or_default();
+        }
+        if let Ok((mut text, reveal)) = text_query.get_mut(dialogue_box.line) {
+            text.text = node.text.clone();
+            if let Some(mut reveal) = reveal {
+                reveal.restart();
+            }
+        }
+        if let Ok((mut text, _)) = text_query.get_mut(dialogue_box.choice_list) {
+            text.text.clear();
+        }
+        return;
+    }
+
+    // only change the choices when they change so they are not laid out again every frame
+    let list = choice_list(node, &dialogue_box.choices, dialogue_box.selected);
+    if let Ok((mut text, _)) = text_query.get_mut(dialogue_box.choice_list) {
+        if text.text != list {
+            text.text = list;
+        }
+    }
+}
@@ -0,0 +1,207 @@
+use bevy::{prelude::*, asset::{AssetLoader, LoadContext, LoadedAsset, BoxedFuture}, reflect::TypeUuid, utils::HashMap};
+use rand::Rng;
+use serde::Deserialize;
+
+pub struct EncounterPlugin;
+
+// the file every encounter zone is defined in, relative to the assets folder
+const ENCOUNTER_FILE: &str = "data/zones.encounters.ron";
+
+// a group of enemys that can be met together in a zone
+#[derive(Deserialize, Clone, Debug)]
+pub struct EncounterGroup {
+    // ids of the enemys in the EnemyDatabase
+    pub enemies: Vec<String>,
+    // how likely this group is to be picked compared to the other groups of the zone
+    pub weight: u32,
+    // every enemy in the group gets a random level in this range (inclusive)
+    pub min_level: u32,
+    pub max_level: u32,
+}
+
+#[derive(Deserialize, Clone, Debug)]
+pub struct EncounterTable {
+    pub groups: Vec<EncounterGroup>,
+    // after every encounter the number of tiles the player has to walk before the
+    // next one is picked between these, a smaller range means a higher encounter rate
+    pub min_steps: f32,
+    pub max_steps: f32,
+}
+
+impl EncounterTable {
+    // how many tiles the player has to walk before the next encounter
+    pub fn roll_steps<R: Rng + ?Sized>(&self, rng: &mut R) -> f32 {
+        rng.gen_range(self.min_steps..=self.max_steps)
+    }
+
+    // pick a group at random using the group weights and roll a level for each of its enemys,
+    // the table was checked to have a group to pick when it was loaded
+    pub fn roll_encounter<R: Rng + ?Sized>(&self, rng: &mut R) -> Encounter {
+        let total: u32 = self.groups.iter().map(|group| group.weight).sum();
+        let mut roll = rng.gen_range(0..total);
+        let group = self.groups
+            .iter()
+            .find(|group| {
+                if roll < group.weight {
+                    true
+                } else {
+                    roll -= group.weight;
+                    false
+                }
+            })
+            .unwrap();
+
+        let enemies = group.enemies
+            .iter()
+            .map(|id| (id.clone(), rng.gen_range(group.min_level..=group.max_level)))
+            .collect();
+
+        Encounter { enemies }
+    }
+
+    // a table that could not be rolled would have to be skipped every time the player
+    // walks into its zone, so it is turned down when the file is loaded instead
+    fn validate(&self) -> Result<(), String> {
+        if self.groups.iter().map(|group| group.weight).sum::<u32>() == 0 {
+            return Err(String::from("has no groups with any weight"));
+        }
+        if let Some(group) = self.groups.iter().find(|group| group.enemies.is_empty() || group.min_level > group.max_level) {
+            return Err(format!("has a group with no enemys or a min_level above its max_level ({:?})", group.enemies));
+        }
+        if !(self.min_steps >= 0.0 && self.min_steps <= self.max_steps && self.max_steps.is_finite()) {
+            return Err(format!("has steps from {} to {}, they have to go from 0 or more up to the same or more", self.min_steps, self.max_steps));
+        }
+        Ok(())
+    }
+}
+
+// every encounter zone, keyed by the zone name set on the EncounterSpawner tiles
+#[derive(Deserialize, TypeUuid)]
+#[uuid = "c4b8a0f2-3e6d-4a1b-9c7e-5f2d8b6a4e91"]
+pub struct EncounterDatabase {
+    pub zones: HashMap<String, EncounterTable>,
+}
+
+impl EncounterDatabase {
+    fn validate(&self) -> Result<(), String> {
+        for (zone, table) in self.zones.iter() {
+            table.validate().map_err(|error| format!("zone \"{}\" {}", zone, error))?;
+        }
+        Ok(())
+    }
+}
+
+// handle to the loaded encounter database, kept as a resource so the asset is never unloaded
+pub struct EncounterDatabaseHandle(pub Handle<EncounterDatabase>);
+
+// the enemys the player is about to fight, inserted before switching to GameState::Combat
+// and read when the combat state spawns the enemys
+#[derive(Clone, Debug)]
+pub struct Encounter {
+    // the enemy id and level of every enemy in the fight
+    pub enemies: Vec<(String, u32)>,
+}
+
+#[derive(Default)]
+struct EncounterDatabaseLoader;
+
+impl AssetLoader for EncounterDatabaseLoader {
+    fn load<'a>(
+        &'a self,
+        bytes: &'a [u8],
+        load_context: &'a mut LoadContext,
+    ) -> BoxedFuture<'a, Result<(), anyhow::Error>> {
+        Box::pin(async move {
+            let database: EncounterDatabase = ron::de::from_bytes(bytes)?;
+            database.validate().map_err(|error| anyhow::anyhow!("{}, {}", load_context.path().display(), error))?;
+            load_context.set_default_asset(LoadedAsset::new(database));
+            Ok(())
+        })
+    }
+
+    fn extensions(&self) -> &[&str] {
+        &["encounters.ron"]
+    }
+}
+
+impl Plugin for EncounterPlugin {
+    fn build(&self, app: &mut App) {
+        app
+            .add_asset::<EncounterDatabase>()
+            .init_asset_loader::<EncounterDatabaseLoader>()
+            .add_startup_system(load_encounters);
+    }
+}
+
+fn load_encounters(mut commands: Commands, assets: Res<AssetServer>) {
+    commands.insert_resource(EncounterDatabaseHandle(assets.load(ENCOUNTER_FILE)));
+}
+
+#[cfg(test)]
+mod tests {
+    use rand::{rngs::StdRng, SeedableRng};
+
+    use super::*;
+
+    fn group(enemies: &[&str], weight: u32, min_level: u32, max_level: u32) -> EncounterGroup {
+        EncounterGroup { enemies: enemies.iter().map(|id| id.to_string()).collect(), weight, min_level, max_level }
+    }
+
+    fn table(groups: Vec<EncounterGroup>, min_steps: f32, max_steps: f32) -> EncounterTable {
+        EncounterTable { groups, min_steps, max_steps }
+    }
+
+    #[test]
+    fn steps_are_rolled_in_the_range() {
+        let mut rng = StdRng::seed_from_u64(3);
+        let grassland = table(vec![group(&["face"], 1, 1, 1)], 6.0, 14.0);
+        let steps: Vec<f32> = (0..1000).map(|_| grassland.roll_steps(&mut rng)).collect();
+        assert!(steps.iter().all(|steps| (6.0..=14.0).contains(steps)));
+        // spread over the whole range, not stuck at one end
+        assert!(steps.iter().any(|&steps| steps < 7.0) && steps.iter().any(|&steps| steps > 13.0));
+
+        let fixed = table(vec![group(&["face"], 1, 1, 1)], 5.0, 5.0);
+        assert_eq!(fixed.roll_steps(&mut rng), 5.0);
+    }
+
+    #[test]
+    fn groups_are_picked_by_weight() {
+        let mut rng = StdRng::seed_from_u64(3);
+        let zone = table(vec![group(&["face"], 4, 1, 1), group(&["face", "face"], 1, 1, 1), group(&["never"], 0, 1, 1)], 1.0, 1.0);
+        let pairs = (0..10000)
+            .map(|_| zone.roll_encounter(&mut rng).enemies)
+            .inspect(|enemies| assert!(enemies.iter().all(|(id, _)| id == "face")))
+            .filter(|enemies| enemies.len() == 2)
+            .count();
+        // 1 out of every 5 encounters
+        assert!((1800..2200).contains(&pairs), "{} pairs", pairs);
+    }
+
+    #[test]
+    fn every_enemy_gets_a_level_in_the_range_of_its_group() {
+        let mut rng = StdRng::seed_from_u64(3);
+        let zone = table(vec![group(&["face", "face", "face"], 1, 2, 4)], 1.0, 1.0);
+        let levels: Vec<u32> = (0..300).flat_map(|_| zone.roll_encounter(&mut rng).enemies).map(|(_, level)| level).collect();
+        for level in 2..=4 {
+            assert!(levels.contains(&level), "level {} was never rolled", level);
+        }
+        assert!(levels.iter().all(|level| (2..=4).contains(level)));
+    }
+
+    #[test]
+    fn tables_that_can_not_be_rolled_are_rejected() {
+        assert!(table(vec![group(&["face"], 1, 1, 2)], 0.0, 3.0).validate().is_ok());
+        assert!(table(Vec::new(), 1.0, 2.0).validate().is_err());
+        assert!(table(vec![group(&["face"], 0, 1, 1)], 1.0, 2.0).validate().is_err());
+        assert!(table(vec![group(&[], 1, 1, 1)], 1.0, 2.0).validate().is_err());
+        assert!(table(vec![group(&["face"], 1, 3, 2)], 1.0, 2.0).validate().is_err());
+        assert!(table(vec![group(&["face"], 1, 1, 1)], 3.0, 2.0).validate().is_err());
+        assert!(table(vec![group(&["face"], 1, 1, 1)], f32::NAN, 2.0).validate().is_err());
+    }
+
+    #[test]
+    fn encounter_file_is_valid() {
+        let database: EncounterDatabase = ron::de::from_str(include_str!("../assets/data/zones.encounters.ron")).unwrap();
+        assert_eq!(database.validate(), Ok(()));
+    }
+}
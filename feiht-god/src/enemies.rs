@@ -0,0 +1,252 @@
+use bevy::{prelude::*, asset::{AssetLoader, LoadContext, LoadedAsset, BoxedFuture}, reflect::TypeUuid, utils::HashMap};
+use rand::Rng;
+use serde::Deserialize;
+
+use crate::stats::{Stats, Status};
+
+pub struct EnemyPlugin;
+
+// the file every enemy is defined in, relative to the assets folder
+const ENEMY_FILE: &str = "data/monsters.enemies.ron";
+
+// one attack an enemy can use on its turn
+#[derive(Deserialize, Clone, Debug)]
+pub struct EnemyMove {
+    pub name: String,
+    // multiplier on the enemys attack, a normal attack is 1.0
+    pub power: f32,
+    // how likely this move is to be picked compared to the other moves of the enemy
+    pub weight: u32,
+    // a status the move puts on the player when it hits
+    #[serde(default)]
+    pub inflicts: Option<Status>,
+}
+
+// an item that might be dropped when the enemy is defeated
+#[derive(Deserialize, Clone, Debug)]
+pub struct LootDrop {
+    pub item: String,
+    // 0.0 never drops, 1.0 always drops
+    pub chance: f64,
+}
+
+// the stats of an enemy before it is spawned, health always starts full
+#[derive(Deserialize, Clone, Debug)]
+pub struct StatBlock {
+    pub max_health: i32,
+    pub attack: i32,
+    pub defense: i32,
+    pub speed: i32,
+}
+
+// every level above 1 adds this fraction of the level 1 stats
+const STATS_PER_LEVEL: f32 = 0.1;
+
+impl StatBlock {
+    // the stat block describes a level 1 enemy, higher levels are scaled up from it
+    pub fn to_stats(&self, level: u32) -> Stats {
+        let scale = 1.0 + STATS_PER_LEVEL * level.saturating_sub(1) as f32;
+        let scaled = |stat: i32| (stat as f32 * scale).round() as i32;
+
+        Stats {
+            health: scaled(self.max_health),
+            max_health: scaled(self.max_health),
+            attack: scaled(self.attack),
+            defense: scaled(self.defense),
+            speed: scaled(self.speed),
+            level,
+        }
+    }
+}
+
+#[derive(Deserialize, Clone, Debug)]
+pub struct EnemyDefinition {
+    pub name: String,
+    // index of the enemy sprite in Sprites.png
+    pub sprite_index: usize,
+    pub scale: f32,
+    pub stats: StatBlock,
+    pub moves: Vec<EnemyMove>,
+    #[serde(default)]
+    pub loot: Vec<LootDrop>,
+    pub xp_reward: u32,
+}
+
+impl EnemyDefinition {
+    // pick a move at random using the move weights, enemys without
+    // any moves fall back to a normal attack
+    pub fn choose_move<R: Rng + ?Sized>(&self, rng: &mut R) -> EnemyMove {
+        let total: u32 = self.moves.iter().map(|enemy_move| enemy_move.weight).sum();
+        if total > 0 {
+            let mut roll = rng.gen_range(0..total);
+            for enemy_move in self.moves.iter() {
+                if roll < enemy_move.weight {
+                    return enemy_move.clone();
+                }
+                roll -= enemy_move.weight;
+            }
+        }
+
+        EnemyMove {
+            name: String::from("Attack"),
+            power: 1.0,
+            weight: 1,
+            inflicts: None,
+        }
+    }
+
+    // roll every entry of the loot table, returning the items that dropped
+    // (the chances were checked to be between 0 and 1 when the file was loaded)
+    pub fn roll_loot<R: Rng + ?Sized>(&self, rng: &mut R) -> Vec<String> {
+        self.loot
+            .iter()
+            .filter(|drop| rng.gen_bool(drop.chance))
+            .map(|drop| drop.item.clone())
+            .collect()
+    }
+}
+
+// every enemy in the game, keyed by the id used to refer to it from other data files
+#[derive(Deserialize, TypeUuid)]
+#[uuid = "5d1a3c9e-8f0b-4f52-9a57-2f64c8a1e3b7"]
+pub struct EnemyDatabase {
+    pub enemies: HashMap<String, EnemyDefinition>,
+}
+
+impl EnemyDatabase {
+    // a chance outside of 0 to 1 (or NaN) would panic when the loot is rolled
+    fn validate(&self) -> Result<(), String> {
+        for (id, enemy) in self.enemies.iter() {
+            if let Some(drop) = enemy.loot.iter().find(|drop| !(0.0..=1.0).contains(&drop.chance)) {
+                return Err(format!("\"{}\" drops \"{}\" with a chance of {}, it has to be from 0 to 1", id, drop.item, drop.chance));
+            }
+        }
+        Ok(())
+    }
+}
+
+// handle to the loaded enemy database, kept as a resource so the asset is never unloaded
+pub struct EnemyDatabaseHandle(pub Handle<EnemyDatabase>);
+
+#[derive(Default)]
+struct EnemyDatabaseLoader;
+
+impl AssetLoader for EnemyDatabaseLoader {
+    fn load<'a>(
+        &'a self,
+        bytes: &'a [u8],
+        load_context: &'a mut LoadContext,
+    ) -> BoxedFuture<'a, Result<(), anyhow::Error>> {
+        Box::pin(async move {
+            let database: EnemyDatabase = ron::de::from_bytes(bytes)?;
+            database.validate().map_err(|error| anyhow::anyhow!("{}, {}", load_context.path().display(), error))?;
+            load_context.set_default_asset(LoadedAsset::new(database));
+            Ok(())
+        })
+    }
+
+    fn extensions(&self) -> &[&str] {
+        &["enemies.ron"]
+    }
+}
+
+impl Plugin for EnemyPlugin {
+    fn build(&self, app: &mut App) {
+        app
+            .add_asset::<EnemyDatabase>()
+            .init_asset_loader::<EnemyDatabaseLoader>()
+            .add_startup_system(load_enemies);
+    }
+}
+
+fn load_enemies(mut commands: Commands, assets: Res<AssetServer>) {
+    commands.insert_resource(EnemyDatabaseHandle(assets.load(ENEMY_FILE)));
+}
+
+#[cfg(test)]
+mod tests {
+    use rand::{rngs::StdRng, SeedableRng};
+
+    use super::*;
+
+    fn enemy_move(name: &str, weight: u32) -> EnemyMove {
+        EnemyMove { name: name.to_string(), power: 1.0, weight, inflicts: None }
+    }
+
+    fn enemy(moves: Vec<EnemyMove>, loot: Vec<LootDrop>) -> EnemyDefinition {
+        EnemyDefinition {
+            name: String::from("Test"),
+            sprite_index: 0,
+            scale: 1.0,
+            stats: StatBlock { max_health: 30, attack: 6, defense: 1, speed: 3 },
+            moves,
+            loot,
+            xp_reward: 1,
+        }
+    }
+
+    fn database(loot: &str) -> Result<(), String> {
+        let text = format!(
+            "(enemies: {{\"test\": (name: \"Test\", sprite_index: 0, scale: 1.0, stats: (max_health: 1, attack: 1, defense: 1, speed: 1), moves: [], loot: [{}], xp_reward: 1)}})",
+            loot
+        );
+        ron::de::from_str::<EnemyDatabase>(&text).unwrap().validate()
+    }
+
+    #[test]
+    fn moves_are_picked_by_weight() {
+        let enemy = enemy(vec![enemy_move("Glare", 3), enemy_move("Headbutt", 1), enemy_move("Never", 0)], Vec::new());
+        let mut rng = StdRng::seed_from_u64(7);
+        let glares = (0..10000).filter(|_| enemy.choose_move(&mut rng).name == "Glare").count();
+        // 3 out of every 4 moves, a move with no weight is never picked
+        assert!((7300..7700).contains(&glares), "{} glares", glares);
+        assert!((0..1000).all(|_| enemy.choose_move(&mut rng).name != "Never"));
+    }
+
+    #[test]
+    fn enemys_without_moves_attack() {
+        let mut rng = StdRng::seed_from_u64(7);
+        for moves in [Vec::new(), vec![enemy_move("Never", 0)]] {
+            let picked = enemy(moves, Vec::new()).choose_move(&mut rng);
+            assert_eq!(picked.name, "Attack");
+            assert_eq!(picked.power, 1.0);
+        }
+    }
+
+    #[test]
+    fn stats_scale_with_the_level() {
+        let block = StatBlock { max_health: 30, attack: 6, defense: 1, speed: 3 };
+        assert_eq!(block.to_stats(1), Stats { health: 30, max_health: 30, attack: 6, defense: 1, speed: 3, level: 1 });
+        // level 0 is treated like level 1
+        assert_eq!(block.to_stats(0).attack, 6);
+        // every level adds a tenth of the level 1 stats, rounded
+        assert_eq!(block.to_stats(6), Stats { health: 45, max_health: 45, attack: 9, defense: 2, speed: 5, level: 6 });
+        assert_eq!(block.to_stats(11), Stats { health: 60, max_health: 60, attack: 12, defense: 2, speed: 6, level: 11 });
+    }
+
+    #[test]
+    fn loot_that_always_or_never_drops() {
+        let enemy = enemy(Vec::new(), vec![
+            LootDrop { item: String::from("potion"), chance: 1.0 },
+            LootDrop { item: String::from("sword"), chance: 0.0 },
+        ]);
+        let mut rng = StdRng::seed_from_u64(7);
+        for _ in 0..100 {
+            assert_eq!(enemy.roll_loot(&mut rng), vec![String::from("potion")]);
+        }
+    }
+
+    #[test]
+    fn loot_chances_have_to_be_from_0_to_1() {
+        assert!(database("(item: \"potion\", chance: 0.0), (item: \"potion\", chance: 1.0)").is_ok());
+        assert!(database("(item: \"potion\", chance: 1.5)").is_err());
+        assert!(database("(item: \"potion\", chance: -0.1)").is_err());
+        assert!(database("(item: \"potion\", chance: NaN)").is_err());
+    }
+
+    #[test]
+    fn enemy_file_is_valid() {
+        let database: EnemyDatabase = ron::de::from_str(include_str!("../assets/data/monsters.enemies.ron")).unwrap();
+        assert_eq!(database.validate(), Ok(()));
+    }
+}
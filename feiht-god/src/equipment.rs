@@ -0,0 +1,182 @@
+// The weapon, armor and accessory the player is wearing. Equipped items add their bonus to the
+// stats of the player (see derive_stats) and are drawn over the player as children of the player
+// entity, so they are hidden and shown with it.
+
+use bevy::prelude::*;
+use serde::{Deserialize, Serialize};
+
+use crate::{player::Player, sprites::{SpriteSheet, spawn_sprite}, stats::{Stats, BaseStats, Buffs, StatBonus, derive_stats}, items::{ItemDatabase, ItemDatabaseHandle, Inventory}};
+
+pub struct EquipmentPlugin;
+
+#[derive(Deserialize, Clone, Copy, PartialEq, Eq, Debug)]
+pub enum EquipmentSlot {
+    Weapon,
+    Armor,
+    Accessory,
+}
+
+impl EquipmentSlot {
+    pub const ALL: [EquipmentSlot; 3] = [
+        EquipmentSlot::Weapon,
+        EquipmentSlot::Armor,
+        EquipmentSlot::Accessory,
+    ];
+
+    // how far in front of the player the overlay is drawn, the weapon is held in front of the armor
+    fn overlay_z(self) -> f32 {
+        match self {
+            EquipmentSlot::Armor => 0.1,
+            EquipmentSlot::Accessory => 0.2,
+            EquipmentSlot::Weapon => 0.3,
+        }
+    }
+}
+
+// the id in the ItemDatabase of the item in every slot, None if nothing is equipped there
+#[derive(Component, Serialize, Deserialize, Clone, Default, Debug, PartialEq)]
+pub struct Equipment {
+    pub weapon: Option<String>,
+    pub armor: Option<String>,
+    pub accessory: Option<String>,
+}
+
+impl Equipment {
+    pub fn get(&self, slot: EquipmentSlot) -> Option<&String> {
+        match slot {
+            EquipmentSlot::Weapon => self.weapon.as_ref(),
+            EquipmentSlot::Armor => self.armor.as_ref(),
+            EquipmentSlot::Accessory => self.accessory.as_ref(),
+        }
+    }
+
+    fn slot_mut(&mut self, slot: EquipmentSlot) -> &mut Option<String> {
+        match slot {
+            EquipmentSlot::Weapon => &mut self.weapon,
+            EquipmentSlot::Armor => &mut self.armor,
+            EquipmentSlot::Accessory => &mut self.accessory,
+        }
+    }
+
+    // every slot that has something in it
+    pub fn equipped(&self) -> impl Iterator<Item = (EquipmentSlot, &String)> {
+        EquipmentSlot::ALL.into_iter().filter_map(|slot| self.get(slot).map(|item| (slot, item)))
+    }
+}
+
+// the sprite of an equipped item drawn over the player
+#[derive(Component)]
+struct EquipmentOverlay;
+
+// put the item in the given slot of the inventory on, the item that was in its slot goes
+// back into the inventory. false if the item can not be equipped
+pub fn equip_item(database: &ItemDatabase, index: usize, inventory: &mut Inventory, equipment: &mut Equipment) -> bool {
+    let id = match inventory.items.get(index) {
+        Some(stack) => stack.item.clone(),
+        None => return false,
+    };
+    let slot = match database.items.get(&id).and_then(|definition| definition.slot()) {
+        Some(slot) => slot,
+        None => return false,
+    };
+    // there has to be room for the item that is taken off
+    if equipment.get(slot).is_some() && !unequip_item(database, slot, inventory, equipment) {
+        return false;
+    }
+
+    inventory.remove(&id, 1);
+    *equipment.slot_mut(slot) = Some(id);
+    true
+}
+
+// take the item in a slot off and put it back into the inventory, false if
+// nothing is equipped there or there is no room for it
+pub fn unequip_item(database: &ItemDatabase, slot: EquipmentSlot, inventory: &mut Inventory, equipment: &mut Equipment) -> bool {
+    let id = match equipment.get(slot) {
+        Some(id) => id.clone(),
+        None => return false,
+    };
+    let max_stack = database.items.get(&id).map_or(1, |definition| definition.max_stack);
+    if inventory.add(&id, 1, max_stack) > 0 {
+        return false;
+    }
+
+    *equipment.slot_mut(slot) = None;
+    true
+}
+
+impl Plugin for EquipmentPlugin {
+    fn build(&self, app: &mut App) {
+        app
+            .add_system(update_player_stats)
+            .add_system(update_equipment_overlays);
+    }
+}
+
+// work the stats of the player out again whenever something they are worked out from changes
+#[allow(clippy::type_complexity)]
+fn update_player_stats(
+    mut player_query: Query<(&mut Stats, &BaseStats, &Equipment, &Buffs), With<Player>>,
+    changed_query: Query<(), (With<Player>, Or<(Changed<BaseStats>, Changed<Equipment>, Changed<Buffs>)>)>,
+    // the bonuses of the equipment are read from the item database, which might load after the player is spawned
+    mut item_events: EventReader<AssetEvent<ItemDatabase>>,
+    item_database_handle: Res<ItemDatabaseHandle>,
+    item_databases: Res<Assets<ItemDatabase>>
+) {
+    let database_changed = item_events.iter().count() > 0;
+    if changed_query.is_empty() && !database_changed { return; }
+
+    let database = item_databases.get(&item_database_handle.0);
+    for (mut stats, base, equipment, buffs) in player_query.iter_mut() {
+        let bonuses: Vec<&StatBonus> = equipment
+            .equipped()
+            .filter_map(|(_, id)| database.and_then(|database| database.items.get(id)))
+            .map(|definition| &definition.bonus)
+            .collect();
+        let derived = derive_stats(base, &bonuses, buffs, &stats);
+        if *stats != derived {
+            *stats = derived;
+        }
+    }
+}
+
+#[allow(clippy::too_many_arguments)]
+fn update_equipment_overlays(
+    // commands are used to despawn the old overlays and spawn the new ones
+    mut commands: Commands,
+    // the overlays are shown and hidden with the player, so they start out the same as it
+    player_query: Query<(Entity, &Equipment, &Visibility, Option<&Children>), With<Player>>,
+    changed_query: Query<(), (With<Player>, Changed<Equipment>)>,
+    overlay_query: Query<(), With<EquipmentOverlay>>,
+    // the overlay sprites are read from the item database, which might load after the player is spawned
+    mut item_events: EventReader<AssetEvent<ItemDatabase>>,
+    item_database_handle: Res<ItemDatabaseHandle>,
+    item_databases: Res<Assets<ItemDatabase>>,
+    sprite_sheet: Res<SpriteSheet>
+) {
+    let database_changed = item_events.iter().count() > 0;
+    if changed_query.is_empty() && !database_changed { return; }
+
+    let database = item_databases.get(&item_database_handle.0);
+    for (player, equipment, visibility, children) in player_query.iter() {
+        for &child in children.iter().flat_map(|children| children.iter()) {
+            if overlay_query.get(child).is_ok() {
+                commands.entity(child).despawn_recursive();
+            }
+        }
+
+        for (slot, id) in equipment.equipped() {
+            let overlay = match database.and_then(|database| database.items.get(id)).and_then(|definition| definition.overlay) {
+                Some(overlay) => overlay,
+                None => continue,
+            };
+            // the overlay is a child of the player so its translation is relative to the player
+            let sprite = spawn_sprite(&mut commands, &sprite_sheet, overlay, Vec3::new(0.0, 0.0, slot.overlay_z()), 1.0);
+            commands.entity(sprite)
+                .insert(EquipmentOverlay)
+                .insert(Visibility { is_visible: visibility.is_visible })
+                .insert(Name::new(format!("{:?} Overlay", slot)));
+            commands.entity(player).add_child(sprite);
+        }
+    }
+}
@@ -1,14 +1,18 @@
 use bevy::prelude::*;
 
-use crate::{GameState, sprites::SpriteSheet};
+use crate::{GameState, sprites::SpriteSheet, tilemap::ChangeMap};
 
 pub struct FadeoutPlugin;
 
+// the game can not be paused while this is on screen
 #[derive(Component)]
-struct ScreenFade {
+pub struct ScreenFade {
     alpha: f32,
     sent: bool,
-    next_state: GameState,
+    // the state that is switched to when the screen is fully covered
+    next_state: Option<GameState>,
+    // the map that is switched to when the screen is fully covered
+    change_map: Option<ChangeMap>,
     timer: Timer
 }
 
@@ -27,6 +31,8 @@ fn fadeout(
     mut fade_query: Query<(Entity, &mut ScreenFade, &mut TextureAtlasSprite)>,
     // get the current game state so we can set the state once the fade is done
     mut state: ResMut<State<GameState>>,
+    // warps change the map instead of the state
+    mut change_map_events: EventWriter<ChangeMap>,
     // we also need the time resource because a timer will be used
     time: Res<Time>
 ) {
@@ -47,8 +53,13 @@ fn fadeout(
 
         // if the timer is more than halfway done and the fade has not been sent ...
         if fade.timer.percent() > 0.5 && !fade.sent {
-            // change the state
-            state.set(fade.next_state).unwrap();
+            // change the state and/or the map
+            if let Some(next_state) = fade.next_state {
+                state.set(next_state).unwrap();
+            }
+            if let Some(change_map) = fade.change_map.take() {
+                change_map_events.send(change_map);
+            }
             // the fade has been sent
             fade.sent = true;
         }
@@ -65,6 +76,34 @@ pub fn create_fadeout(
     commands: &mut Commands, 
     next_state: GameState, 
     sprite_sheet: &Res<SpriteSheet>
+) {
+    spawn_fade(commands, Some(next_state), None, sprite_sheet);
+}
+
+// fade out, go to another map and fade back in without changing the state
+pub fn create_warp_fadeout(
+    commands: &mut Commands,
+    change_map: ChangeMap,
+    sprite_sheet: &Res<SpriteSheet>
+) {
+    spawn_fade(commands, None, Some(change_map), sprite_sheet);
+}
+
+// fade out, change the state and the map together and fade back in, for starting a game from the title screen
+pub fn create_map_fadeout(
+    commands: &mut Commands,
+    next_state: GameState,
+    change_map: ChangeMap,
+    sprite_sheet: &Res<SpriteSheet>
+) {
+    spawn_fade(commands, Some(next_state), Some(change_map), sprite_sheet);
+}
+
+fn spawn_fade(
+    commands: &mut Commands,
+    next_state: Option<GameState>,
+    change_map: Option<ChangeMap>,
+    sprite_sheet: &Res<SpriteSheet>
 ) {
     let mut sprite = TextureAtlasSprite::new(0);
     sprite.color = Color::rgba(0.1, 0.1, 0.15, 0.0);
@@ -84,6 +123,7 @@ pub fn create_fadeout(
             alpha: 0.0,
             sent: false,
             next_state,
+            change_map,
             timer: Timer::from_seconds(1.0, false),
         })
         .insert(Name::new("Fadeout"));
@@ -0,0 +1,67 @@
+use bevy::prelude::*;
+
+use crate::{GameState, input::{ActionState, InputAction}, stats::Stats, player::Player, sprites::SpriteSheet, fadeout::create_fadeout};
+
+pub struct GameOverPlugin;
+
+// marker for everything spawned for the game over screen
+#[derive(Component)]
+struct GameOverScreen;
+
+impl Plugin for GameOverPlugin {
+    fn build(&self, app: &mut App) {
+        app
+            .add_system_set(SystemSet::on_update(GameState::GameOver).with_system(game_over_input))
+            .add_system_set(SystemSet::on_enter(GameState::GameOver).with_system(spawn_game_over_screen))
+            .add_system_set(SystemSet::on_exit(GameState::GameOver).with_system(despawn_game_over_screen));
+    }
+}
+
+fn spawn_game_over_screen(
+    mut commands: Commands,
+    // the screen is drawn wherever the camera currently is
+    camera_query: Query<&Transform, With<Camera>>,
+    sprite_sheet: Res<SpriteSheet>
+) {
+    let camera_translation = camera_query.single().translation;
+
+    // sprite index 0 is a solid tile, tint it and stretch it over the whole screen
+    let mut sprite = TextureAtlasSprite::new(0);
+    sprite.color = Color::rgb(0.3, 0.0, 0.0);
+    sprite.custom_size = Some(Vec2::splat(4.0));
+
+    commands
+        .spawn_bundle(SpriteSheetBundle {
+            sprite,
+            texture_atlas: sprite_sheet.0.clone(),
+            transform: Transform {
+                translation: Vec3::new(camera_translation.x, camera_translation.y, 500.0),
+                ..Default::default()
+            },
+            ..Default::default()
+        })
+        .insert(GameOverScreen)
+        .insert(Name::new("Game Over"));
+}
+
+fn despawn_game_over_screen(mut commands: Commands, screen_query: Query<Entity, With<GameOverScreen>>) {
+    for entity in screen_query.iter() {
+        commands.entity(entity).despawn_recursive();
+    }
+}
+
+// pressing confirm revives the player with full health and returns to the overworld
+fn game_over_input(
+    mut commands: Commands,
+    mut actions: ResMut<ActionState>,
+    mut player_query: Query<&mut Stats, With<Player>>,
+    sprite_sheet: Res<SpriteSheet>
+) {
+    if actions.just_pressed(InputAction::Confirm) {
+        let mut stats = player_query.single_mut();
+        stats.health = stats.max_health;
+
+        create_fadeout(&mut commands, GameState::Overworld, &sprite_sheet);
+        actions.clear();
+    }
+}
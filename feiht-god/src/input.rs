@@ -0,0 +1,492 @@
+// Action based input, gameplay systems check if an action (like Confirm) is pressed instead
+// of checking keys so the keys can be changed by the player. The bindings are saved to
+// input.ron in the config folder (~/.config/feiht-god on linux) whenever they are changed
+// and loaded from there when the game starts.
+//
+// Gamepads drive the same actions through their buttons, the left stick also presses the
+// move actions (for menus) and gives an analog movement direction for walking.
+
+use std::{fs, path::PathBuf};
+
+use bevy::{prelude::*, input::InputSystem, utils::{HashMap, HashSet}};
+use serde::{Deserialize, Serialize};
+
+pub struct InputPlugin;
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum InputAction {
+    MoveUp,
+    MoveDown,
+    MoveLeft,
+    MoveRight,
+    // pick the selected option or talk to someone
+    Confirm,
+    // back out of a menu
+    Cancel,
+    // open the pause menu
+    Menu,
+    // swing the sword in the overworld
+    Attack,
+}
+
+impl InputAction {
+    pub const ALL: [InputAction; 8] = [
+        InputAction::MoveUp,
+        InputAction::MoveDown,
+        InputAction::MoveLeft,
+        InputAction::MoveRight,
+        InputAction::Confirm,
+        InputAction::Cancel,
+        InputAction::Menu,
+        InputAction::Attack,
+    ];
+
+    // the gamepad buttons an action is bound to before the player changes anything
+    fn default_buttons(self) -> Vec<GamepadButtonType> {
+        match self {
+            InputAction::MoveUp => vec![GamepadButtonType::DPadUp],
+            InputAction::MoveDown => vec![GamepadButtonType::DPadDown],
+            InputAction::MoveLeft => vec![GamepadButtonType::DPadLeft],
+            InputAction::MoveRight => vec![GamepadButtonType::DPadRight],
+            InputAction::Confirm => vec![GamepadButtonType::South],
+            InputAction::Cancel => vec![GamepadButtonType::East],
+            InputAction::Menu => vec![GamepadButtonType::Start],
+            InputAction::Attack => vec![GamepadButtonType::West],
+        }
+    }
+
+    // the keys an action is bound to before the player changes anything
+    fn default_keys(self) -> Vec<KeyCode> {
+        match self {
+            InputAction::MoveUp => vec![KeyCode::W, KeyCode::Up],
+            InputAction::MoveDown => vec![KeyCode::S, KeyCode::Down],
+            InputAction::MoveLeft => vec![KeyCode::A, KeyCode::Left],
+            InputAction::MoveRight => vec![KeyCode::D, KeyCode::Right],
+            InputAction::Confirm => vec![KeyCode::Space, KeyCode::Return],
+            InputAction::Cancel => vec![KeyCode::X, KeyCode::Back],
+            InputAction::Menu => vec![KeyCode::Escape],
+            InputAction::Attack => vec![KeyCode::C, KeyCode::J],
+        }
+    }
+}
+
+// the stick has to be pushed further than this before it does anything, so a worn
+// stick that does not go back to the center does not walk the player around
+pub const STICK_DEADZONE: f32 = 0.2;
+// how far the stick has to be pushed to press a move action (for moving through menus)
+pub const STICK_PRESS: f32 = 0.5;
+
+fn default_buttons() -> HashMap<InputAction, Vec<GamepadButtonType>> {
+    InputAction::ALL
+        .iter()
+        .map(|&action| (action, action.default_buttons()))
+        .collect()
+}
+
+// the keys and gamepad buttons every action is bound to
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct InputMap {
+    bindings: HashMap<InputAction, Vec<KeyCode>>,
+    // files saved before gamepads were supported have no buttons
+    #[serde(default = "default_buttons")]
+    buttons: HashMap<InputAction, Vec<GamepadButtonType>>,
+}
+
+impl Default for InputMap {
+    fn default() -> Self {
+        InputMap {
+            bindings: InputAction::ALL
+                .iter()
+                .map(|&action| (action, action.default_keys()))
+                .collect(),
+            buttons: default_buttons(),
+        }
+    }
+}
+
+impl InputMap {
+    pub fn keys(&self, action: InputAction) -> &[KeyCode] {
+        self.bindings.get(&action).map(Vec::as_slice).unwrap_or_default()
+    }
+
+    pub fn buttons(&self, action: InputAction) -> &[GamepadButtonType] {
+        self.buttons.get(&action).map(Vec::as_slice).unwrap_or_default()
+    }
+
+    // bind an action to only this key. the key is taken off any other action so one key never
+    // does two things, an action that would be left without a key gets the old keys of this
+    // action instead (so there is always a key for Confirm and Menu). false if there were no
+    // old keys to give it, nothing is changed then
+    pub fn bind(&mut self, action: InputAction, key: KeyCode) -> bool {
+        let old: Vec<KeyCode> = self.keys(action).iter().copied().filter(|&bound| bound != key).collect();
+        let last_key = self.bindings
+            .iter()
+            .any(|(&other, keys)| other != action && keys.as_slice() == [key]);
+        if last_key && old.is_empty() {
+            return false;
+        }
+
+        self.bindings.insert(action, vec![key]);
+        for (&other, keys) in self.bindings.iter_mut() {
+            if other == action || !keys.contains(&key) { continue; }
+            keys.retain(|&bound| bound != key);
+            if keys.is_empty() {
+                keys.extend(old.iter().copied());
+            }
+        }
+        true
+    }
+
+    // every action back on the keys and buttons it had before the player changed anything
+    pub fn reset(&mut self) {
+        *self = InputMap::default();
+        info!("Reset the key bindings");
+        if let Err(error) = self.save() {
+            warn!("Can not save the key bindings: {}", error);
+        }
+    }
+
+    fn config_path() -> Option<PathBuf> {
+        dirs::config_dir().map(|config| config.join("feiht-god").join("input.ron"))
+    }
+
+    // the saved bindings, or the default ones if nothing was saved (or the file is broken)
+    fn load() -> Self {
+        let path = match InputMap::config_path() {
+            Some(path) if path.exists() => path,
+            _ => return InputMap::default(),
+        };
+
+        let mut input_map: InputMap = match fs::read(&path)
+            .map_err(|error| error.to_string())
+            .and_then(|bytes| ron::de::from_bytes(&bytes).map_err(|error| error.to_string()))
+        {
+            Ok(input_map) => input_map,
+            Err(error) => {
+                warn!("Using the default key bindings, can not read {}: {}", path.display(), error);
+                return InputMap::default();
+            }
+        };
+
+        // actions added after the file was saved get their default keys
+        for action in InputAction::ALL {
+            input_map.bindings.entry(action).or_insert_with(|| action.default_keys());
+            input_map.buttons.entry(action).or_insert_with(|| action.default_buttons());
+        }
+        input_map
+    }
+
+    fn save(&self) -> Result<(), String> {
+        let path = InputMap::config_path().ok_or("there is no config folder")?;
+        if let Some(folder) = path.parent() {
+            fs::create_dir_all(folder).map_err(|error| error.to_string())?;
+        }
+        let text = ron::ser::to_string_pretty(self, ron::ser::PrettyConfig::default())
+            .map_err(|error| error.to_string())?;
+        fs::write(&path, text).map_err(|error| error.to_string())
+    }
+}
+
+// which actions are held down this frame, this is what gameplay systems read instead of the keyboard
+#[derive(Default)]
+pub struct ActionState {
+    pressed: HashSet<InputAction>,
+    just_pressed: HashSet<InputAction>,
+    // the move actions the stick was pressing last frame, to tell when the stick just pressed one
+    stick_pressed: HashSet<InputAction>,
+    // the position of the stick past the deadzone, zero when the stick is not being used
+    stick: Vec2,
+}
+
+impl ActionState {
+    // which way to walk and how fast (from 0 to 1), the stick is used when it is pushed
+    // and the move actions otherwise, diagonals are no faster than straight lines
+    pub fn movement(&self) -> Vec2 {
+        if self.stick != Vec2::ZERO {
+            return self.stick;
+        }
+
+        let axis = |positive, negative| {
+            self.pressed(positive) as i32 as f32 - self.pressed(negative) as i32 as f32
+        };
+        Vec2::new(
+            axis(InputAction::MoveRight, InputAction::MoveLeft),
+            axis(InputAction::MoveUp, InputAction::MoveDown),
+        ).normalize_or_zero()
+    }
+
+    pub fn pressed(&self, action: InputAction) -> bool {
+        self.pressed.contains(&action)
+    }
+
+    pub fn just_pressed(&self, action: InputAction) -> bool {
+        self.just_pressed.contains(&action)
+    }
+
+    // forget the actions that were just pressed, so an action is not used twice in one frame
+    // (for example once by combat and then again by the state it switches to)
+    pub fn clear(&mut self) {
+        self.just_pressed.clear();
+    }
+}
+
+// while this is set the next key that is pressed gets bound to the action, a gamepad
+// button stops it without binding anything (gamepads can not be bound to keys)
+#[derive(Default)]
+pub struct Rebinding(pub Option<InputAction>);
+
+impl Plugin for InputPlugin {
+    fn build(&self, app: &mut App) {
+        app
+            .insert_resource(InputMap::load())
+            .init_resource::<ActionState>()
+            .init_resource::<Rebinding>()
+            // the actions are worked out right after bevy reads the keyboard, before any gameplay system runs
+            .add_system_to_stage(CoreStage::PreUpdate, rebind_action.after(InputSystem).label("rebind"))
+            .add_system_to_stage(CoreStage::PreUpdate, update_action_state.after("rebind"));
+    }
+}
+
+// the stick position with the deadzone cut out, so just past the deadzone is 0 and all the way is 1
+fn apply_deadzone(stick: Vec2) -> Vec2 {
+    let length = stick.length();
+    if length <= STICK_DEADZONE {
+        return Vec2::ZERO;
+    }
+    let scaled = ((length - STICK_DEADZONE) / (1.0 - STICK_DEADZONE)).min(1.0);
+    stick / length * scaled
+}
+
+fn update_action_state(
+    keyboard: Res<Input<KeyCode>>,
+    // every connected gamepad can be used at the same time as the keyboard
+    gamepads: Res<Gamepads>,
+    buttons: Res<Input<GamepadButton>>,
+    axes: Res<Axis<GamepadAxis>>,
+    input_map: Res<InputMap>,
+    mut actions: ResMut<ActionState>
+) {
+    actions.pressed.clear();
+    actions.just_pressed.clear();
+
+    // the stick that is pushed the furthest is the one that is used
+    actions.stick = gamepads
+        .iter()
+        .map(|&gamepad| Vec2::new(
+            axes.get(GamepadAxis(gamepad, GamepadAxisType::LeftStickX)).unwrap_or(0.0),
+            axes.get(GamepadAxis(gamepad, GamepadAxisType::LeftStickY)).unwrap_or(0.0),
+        ))
+        .map(apply_deadzone)
+        .fold(Vec2::ZERO, |furthest, stick| if stick.length() > furthest.length() { stick } else { furthest });
+
+    let stick = actions.stick;
+    let stick_pressed: HashSet<InputAction> = [
+        (InputAction::MoveRight, stick.x),
+        (InputAction::MoveLeft, -stick.x),
+        (InputAction::MoveUp, stick.y),
+        (InputAction::MoveDown, -stick.y),
+    ]
+        .into_iter()
+        .filter(|&(_, amount)| amount >= STICK_PRESS)
+        .map(|(action, _)| action)
+        .collect();
+
+    for action in InputAction::ALL {
+        let keys = input_map.keys(action);
+        let action_buttons: Vec<GamepadButton> = gamepads
+            .iter()
+            .flat_map(|&gamepad| input_map.buttons(action).iter().map(move |&button| GamepadButton(gamepad, button)))
+            .collect();
+
+        if keys.iter().any(|&key| keyboard.pressed(key))
+            || action_buttons.iter().any(|&button| buttons.pressed(button))
+            || stick_pressed.contains(&action)
+        {
+            actions.pressed.insert(action);
+        }
+        if keys.iter().any(|&key| keyboard.just_pressed(key))
+            || action_buttons.iter().any(|&button| buttons.just_pressed(button))
+            || (stick_pressed.contains(&action) && !actions.stick_pressed.contains(&action))
+        {
+            actions.just_pressed.insert(action);
+        }
+    }
+
+    actions.stick_pressed = stick_pressed;
+}
+
+fn rebind_action(
+    // the keyboard is mutable so the key is cleared once it has been bound (so it does not
+    // also do whatever it was bound to before), the same goes for the gamepad buttons
+    mut keyboard: ResMut<Input<KeyCode>>,
+    mut buttons: ResMut<Input<GamepadButton>>,
+    mut input_map: ResMut<InputMap>,
+    mut rebinding: ResMut<Rebinding>
+) {
+    let action = match rebinding.0 {
+        Some(action) => action,
+        None => return,
+    };
+    // otherwise a player with only a gamepad would be stuck waiting for a key
+    if buttons.get_just_pressed().next().is_some() {
+        info!("Stopped binding {:?}", action);
+        rebinding.0 = None;
+        buttons.clear();
+        return;
+    }
+    let key = match keyboard.get_just_pressed().next() {
+        Some(&key) => key,
+        None => return,
+    };
+
+    if input_map.bind(action, key) {
+        info!("Bound {:?} to {:?}", key, action);
+        if let Err(error) = input_map.save() {
+            warn!("Can not save the key bindings: {}", error);
+        }
+    } else {
+        warn!("Can not bind {:?} to {:?}, it is the only key of another action", key, action);
+    }
+
+    rebinding.0 = None;
+    keyboard.clear();
+}
+
+#[cfg(test)]
+mod tests {
+    use bevy::{ecs::event::Events, input::{InputPlugin as BevyInputPlugin, gamepad::{GamepadEventRaw, GamepadEventType}}};
+
+    use super::*;
+
+    const GAMEPAD: Gamepad = Gamepad(0);
+
+    // a headless app with the input plugins and one gamepad plugged in
+    fn gamepad_app() -> App {
+        let mut app = App::new();
+        app
+            .add_plugins(MinimalPlugins)
+            .add_plugin(BevyInputPlugin)
+            .add_plugin(InputPlugin)
+            // the bindings saved on this machine are not used
+            .insert_resource(InputMap::default());
+        send(&mut app, GamepadEventType::Connected);
+        app
+    }
+
+    // gilrs sends raw events, bevy turns them into GamepadEvents and the button and axis input
+    fn send(app: &mut App, event: GamepadEventType) {
+        app.world.resource_mut::<Events<GamepadEventRaw>>().send(GamepadEventRaw(GAMEPAD, event));
+        app.update();
+    }
+
+    fn actions(app: &App) -> &ActionState {
+        app.world.resource::<ActionState>()
+    }
+
+    #[test]
+    fn gamepad_button_stops_rebinding() {
+        let mut app = gamepad_app();
+        app.insert_resource(Rebinding(Some(InputAction::Attack)));
+        send(&mut app, GamepadEventType::ButtonChanged(GamepadButtonType::East, 1.0));
+        assert_eq!(app.world.resource::<Rebinding>().0, None);
+        // the button does not also back out of the menu
+        assert!(!actions(&app).just_pressed(InputAction::Cancel));
+        assert_eq!(app.world.resource::<InputMap>().keys(InputAction::Attack), [KeyCode::C, KeyCode::J]);
+    }
+
+    #[test]
+    fn gamepad_buttons_press_actions() {
+        let mut app = gamepad_app();
+        send(&mut app, GamepadEventType::ButtonChanged(GamepadButtonType::South, 1.0));
+        assert!(actions(&app).pressed(InputAction::Confirm));
+        assert!(actions(&app).just_pressed(InputAction::Confirm));
+        assert!(!actions(&app).pressed(InputAction::Cancel));
+
+        // held down, but not just pressed any more
+        app.update();
+        assert!(actions(&app).pressed(InputAction::Confirm));
+        assert!(!actions(&app).just_pressed(InputAction::Confirm));
+
+        send(&mut app, GamepadEventType::ButtonChanged(GamepadButtonType::South, 0.0));
+        assert!(!actions(&app).pressed(InputAction::Confirm));
+    }
+
+    #[test]
+    fn left_stick_moves_and_presses_move_actions() {
+        let mut app = gamepad_app();
+        send(&mut app, GamepadEventType::AxisChanged(GamepadAxisType::LeftStickX, 1.0));
+        assert_eq!(actions(&app).movement(), Vec2::new(1.0, 0.0));
+        assert!(actions(&app).pressed(InputAction::MoveRight));
+        assert!(actions(&app).just_pressed(InputAction::MoveRight));
+
+        app.update();
+        assert!(!actions(&app).just_pressed(InputAction::MoveRight));
+
+        send(&mut app, GamepadEventType::AxisChanged(GamepadAxisType::LeftStickX, 0.0));
+        assert_eq!(actions(&app).movement(), Vec2::ZERO);
+        assert!(!actions(&app).pressed(InputAction::MoveRight));
+    }
+
+    #[test]
+    fn stick_pushed_part_of_the_way_walks_slower() {
+        let mut app = gamepad_app();
+        send(&mut app, GamepadEventType::AxisChanged(GamepadAxisType::LeftStickY, -0.6));
+        let movement = actions(&app).movement();
+        assert!((movement.y + 0.5).abs() < 0.01, "{}", movement);
+        assert!(actions(&app).pressed(InputAction::MoveDown));
+
+        // inside the deadzone does nothing
+        send(&mut app, GamepadEventType::AxisChanged(GamepadAxisType::LeftStickY, -STICK_DEADZONE / 2.0));
+        assert_eq!(actions(&app).movement(), Vec2::ZERO);
+        assert!(!actions(&app).pressed(InputAction::MoveDown));
+    }
+
+    #[test]
+    fn binding_replaces_the_keys_of_the_action() {
+        let mut input_map = InputMap::default();
+        assert!(input_map.bind(InputAction::Attack, KeyCode::K));
+        assert_eq!(input_map.keys(InputAction::Attack), [KeyCode::K]);
+    }
+
+    #[test]
+    fn binding_takes_the_key_off_other_actions() {
+        let mut input_map = InputMap::default();
+        assert!(input_map.bind(InputAction::Attack, KeyCode::Space));
+        assert_eq!(input_map.keys(InputAction::Attack), [KeyCode::Space]);
+        assert_eq!(input_map.keys(InputAction::Confirm), [KeyCode::Return]);
+    }
+
+    #[test]
+    fn binding_the_last_key_of_an_action_swaps_the_keys() {
+        let mut input_map = InputMap::default();
+        assert!(input_map.bind(InputAction::Attack, KeyCode::Escape));
+        assert_eq!(input_map.keys(InputAction::Attack), [KeyCode::Escape]);
+        assert_eq!(input_map.keys(InputAction::Menu), [KeyCode::C, KeyCode::J]);
+
+        // taking both keys of Confirm leaves it with the old keys of the action
+        assert!(input_map.bind(InputAction::MoveUp, KeyCode::Space));
+        assert!(input_map.bind(InputAction::Cancel, KeyCode::Return));
+        assert_eq!(input_map.keys(InputAction::Confirm), [KeyCode::X, KeyCode::Back]);
+    }
+
+    #[test]
+    fn binding_is_refused_when_no_key_could_be_swapped() {
+        let mut input_map = InputMap::default();
+        input_map.bindings.insert(InputAction::Attack, Vec::new());
+        assert!(!input_map.bind(InputAction::Attack, KeyCode::Escape));
+        assert_eq!(input_map.keys(InputAction::Menu), [KeyCode::Escape]);
+        assert!(input_map.keys(InputAction::Attack).is_empty());
+    }
+
+    #[test]
+    fn every_action_always_has_a_key() {
+        let mut input_map = InputMap::default();
+        let keys = [KeyCode::Space, KeyCode::Return, KeyCode::Escape, KeyCode::X, KeyCode::W, KeyCode::Up];
+        for (i, &action) in InputAction::ALL.iter().cycle().take(40).enumerate() {
+            input_map.bind(action, keys[i % keys.len()]);
+            for action in InputAction::ALL {
+                assert!(!input_map.keys(action).is_empty(), "{:?} has no key", action);
+            }
+        }
+    }
+}
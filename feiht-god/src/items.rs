@@ -0,0 +1,247 @@
+// Items the player carries. Every item is defined once in items.items.ron (its name, what it does
+// when it is used or the bonus it gives when it is worn), the Inventory only keeps the ids and
+// counts of the items so it can be saved without the definitions.
+
+use bevy::{prelude::*, asset::{AssetLoader, LoadContext, LoadedAsset, BoxedFuture}, reflect::TypeUuid, utils::HashMap};
+use serde::{Deserialize, Serialize};
+
+use crate::{stats::{Stats, Status, StatusEffects, Buffs, StatBonus}, equipment::EquipmentSlot, menu::MenuOption};
+
+pub struct ItemPlugin;
+
+// the file every item is defined in, relative to the assets folder
+const ITEM_FILE: &str = "data/items.items.ron";
+
+#[derive(Deserialize, Clone, Copy, PartialEq, Eq, Debug)]
+pub enum ItemKind {
+    // used up when it is used
+    Consumable,
+    // opens doors and the like, can not be used from the inventory
+    Key,
+    // worn in one of the equipment slots instead of being used
+    Equipment(EquipmentSlot),
+}
+
+// the stats a buff can raise
+#[derive(Deserialize, Clone, Copy, PartialEq, Eq, Debug)]
+pub enum BuffStat {
+    Attack,
+    Defense,
+}
+
+#[derive(Deserialize, Clone, Copy, PartialEq, Eq, Debug)]
+pub enum ItemEffect {
+    // heal this much health
+    Heal(i32),
+    // raise a stat until the end of the battle
+    Buff { stat: BuffStat, amount: i32 },
+    Cure(Status),
+}
+
+fn default_max_stack() -> u32 {
+    99
+}
+
+#[derive(Deserialize, Clone, Debug)]
+pub struct ItemDefinition {
+    pub name: String,
+    pub kind: ItemKind,
+    #[serde(default)]
+    pub effects: Vec<ItemEffect>,
+    // the most of this item the player can carry
+    #[serde(default = "default_max_stack")]
+    pub max_stack: u32,
+    // items that can only be used in combat (like buffs, which only last until the end of the battle)
+    #[serde(default)]
+    pub battle_only: bool,
+    // added to the stats of the player while the item is equipped
+    #[serde(default)]
+    pub bonus: StatBonus,
+    // index of the sprite in Sprites.png drawn over the player while the item is equipped
+    #[serde(default)]
+    pub overlay: Option<usize>,
+}
+
+impl ItemDefinition {
+    // the slot the item is worn in, None if it can not be equipped
+    pub fn slot(&self) -> Option<EquipmentSlot> {
+        match self.kind {
+            ItemKind::Equipment(slot) => Some(slot),
+            ItemKind::Consumable | ItemKind::Key => None,
+        }
+    }
+
+    // true if using the item now would do something, so an item is never wasted
+    pub fn can_use(&self, stats: &Stats, statuses: &StatusEffects, in_battle: bool) -> bool {
+        if self.kind != ItemKind::Consumable || (self.battle_only && !in_battle) {
+            return false;
+        }
+        self.effects.iter().any(|effect| match *effect {
+            ItemEffect::Heal(_) => stats.health < stats.max_health,
+            ItemEffect::Buff { .. } => in_battle,
+            ItemEffect::Cure(status) => statuses.has(status),
+        })
+    }
+
+    // buffs are only passed in during a battle, the stats are worked out again with them once they change
+    fn apply(&self, stats: &mut Stats, statuses: &mut StatusEffects, mut buffs: Option<&mut Buffs>) {
+        for effect in self.effects.iter() {
+            match *effect {
+                ItemEffect::Heal(amount) => stats.heal(amount),
+                ItemEffect::Buff { stat, amount } => {
+                    if let Some(buffs) = buffs.as_deref_mut() {
+                        match stat {
+                            BuffStat::Attack => buffs.attack += amount,
+                            BuffStat::Defense => buffs.defense += amount,
+                        }
+                    }
+                }
+                ItemEffect::Cure(status) => {
+                    statuses.cure(status);
+                }
+            }
+        }
+    }
+}
+
+// every item in the game, keyed by the id used to refer to it from other data files
+#[derive(Deserialize, TypeUuid)]
+#[uuid = "0d444c76-64af-427a-ba45-6aff9a1ae367"]
+pub struct ItemDatabase {
+    pub items: HashMap<String, ItemDefinition>,
+}
+
+// handle to the loaded item database, kept as a resource so the asset is never unloaded
+pub struct ItemDatabaseHandle(pub Handle<ItemDatabase>);
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub struct ItemStack {
+    // id of the item in the ItemDatabase
+    pub item: String,
+    pub count: u32,
+}
+
+// the items the player is carrying, in the order they were picked up
+#[derive(Component, Serialize, Deserialize, Clone, Default, Debug, PartialEq)]
+pub struct Inventory {
+    pub items: Vec<ItemStack>,
+}
+
+impl Inventory {
+    // add as many of the item as fit under its max stack, returning how many did not fit
+    pub fn add(&mut self, item: &str, count: u32, max_stack: u32) -> u32 {
+        let index = match self.items.iter().position(|stack| stack.item == item) {
+            Some(index) => index,
+            None => {
+                self.items.push(ItemStack { item: item.to_string(), count: 0 });
+                self.items.len() - 1
+            }
+        };
+        let stack = &mut self.items[index];
+        let added = count.min(max_stack.saturating_sub(stack.count));
+        stack.count += added;
+        if stack.count == 0 {
+            self.items.remove(index);
+        }
+        count - added
+    }
+
+    // false (and nothing is taken) if there are not that many of the item
+    pub fn remove(&mut self, item: &str, count: u32) -> bool {
+        let index = match self.items.iter().position(|stack| stack.item == item && stack.count >= count) {
+            Some(index) => index,
+            None => return false,
+        };
+        self.items[index].count -= count;
+        if self.items[index].count == 0 {
+            self.items.remove(index);
+        }
+        true
+    }
+}
+
+// use one of the item in the given slot of the inventory, false if it would do nothing.
+// buffs are only passed in during a battle
+pub fn use_item(
+    database: &ItemDatabase,
+    index: usize,
+    inventory: &mut Inventory,
+    stats: &mut Stats,
+    statuses: &mut StatusEffects,
+    buffs: Option<&mut Buffs>
+) -> bool {
+    let id = match inventory.items.get(index) {
+        Some(stack) => stack.item.clone(),
+        None => return false,
+    };
+    let definition = match database.items.get(&id) {
+        Some(definition) => definition,
+        None => {
+            warn!("Item \"{}\" is not defined", id);
+            return false;
+        }
+    };
+    if !definition.can_use(stats, statuses, buffs.is_some()) { return false; }
+
+    inventory.remove(&id, 1);
+    definition.apply(stats, statuses, buffs);
+    info!("Used {}", definition.name);
+    true
+}
+
+// the options of an inventory menu, one for every stack of items then Back, only the
+// items that would do something right now (or can be equipped outside of battle) can be picked
+pub fn inventory_options(
+    database: Option<&ItemDatabase>,
+    inventory: &Inventory,
+    stats: &Stats,
+    statuses: &StatusEffects,
+    in_battle: bool
+) -> Vec<MenuOption> {
+    inventory.items
+        .iter()
+        .map(|stack| {
+            let definition = database.and_then(|database| database.items.get(&stack.item));
+            let name = definition.map_or(stack.item.as_str(), |definition| definition.name.as_str());
+            let usable = definition.is_some_and(|definition| {
+                definition.can_use(stats, statuses, in_battle) || (definition.slot().is_some() && !in_battle)
+            });
+            MenuOption::new(&format!("{:<16}x{}", name, stack.count), usable)
+        })
+        .chain(std::iter::once(MenuOption::new("Back", true)))
+        .collect()
+}
+
+#[derive(Default)]
+struct ItemDatabaseLoader;
+
+impl AssetLoader for ItemDatabaseLoader {
+    fn load<'a>(
+        &'a self,
+        bytes: &'a [u8],
+        load_context: &'a mut LoadContext,
+    ) -> BoxedFuture<'a, Result<(), anyhow::Error>> {
+        Box::pin(async move {
+            let database: ItemDatabase = ron::de::from_bytes(bytes)?;
+            load_context.set_default_asset(LoadedAsset::new(database));
+            Ok(())
+        })
+    }
+
+    fn extensions(&self) -> &[&str] {
+        &["items.ron"]
+    }
+}
+
+impl Plugin for ItemPlugin {
+    fn build(&self, app: &mut App) {
+        app
+            .add_asset::<ItemDatabase>()
+            .init_asset_loader::<ItemDatabaseLoader>()
+            .add_startup_system(load_items);
+    }
+}
+
+fn load_items(mut commands: Commands, assets: Res<AssetServer>) {
+    commands.insert_resource(ItemDatabaseHandle(assets.load(ITEM_FILE)));
+}
@@ -0,0 +1,256 @@
+// Experience and levels. The xp needed to reach every level and how much each stat grows on a
+// level up come from the growth curves in player.growth.ron, so the pace of the game can be
+// changed without touching the code. Xp is gained from battles and from enemies beaten with the sword.
+
+use bevy::{prelude::*, asset::{AssetLoader, LoadContext, LoadedAsset, BoxedFuture}, reflect::TypeUuid};
+use serde::{Deserialize, Serialize};
+
+use crate::stats::{Stats, BaseStats};
+
+pub struct LevelingPlugin;
+
+// the file the growth curves of the player are defined in, relative to the assets folder
+const GROWTH_FILE: &str = "data/player.growth.ron";
+
+fn default_exponent() -> f32 {
+    1.0
+}
+
+// how much something grows as the player levels up, it has grown by
+// per_level * (level - 1) ^ exponent since level 1
+#[derive(Deserialize, Clone, Copy, Debug)]
+pub struct GrowthCurve {
+    pub per_level: f32,
+    // 1.0 grows by the same amount every level, higher grows more at higher levels
+    #[serde(default = "default_exponent")]
+    pub exponent: f32,
+}
+
+impl GrowthCurve {
+    // how much has been gained from level 1 up to the level
+    pub fn total(&self, level: u32) -> i32 {
+        (self.per_level * (level.saturating_sub(1) as f32).powf(self.exponent)).round() as i32
+    }
+
+    // how much is gained going up to the level from the one below it
+    pub fn gain(&self, level: u32) -> i32 {
+        self.total(level) - self.total(level.saturating_sub(1))
+    }
+}
+
+#[derive(Deserialize, TypeUuid)]
+#[uuid = "c4b1e8a2-3d6f-4e59-8f7a-1b2c9d0e5a64"]
+pub struct GrowthCurves {
+    pub max_level: u32,
+    // the total xp needed to reach a level
+    pub xp: GrowthCurve,
+    pub max_health: GrowthCurve,
+    pub attack: GrowthCurve,
+    pub defense: GrowthCurve,
+    pub speed: GrowthCurve,
+}
+
+// handle to the loaded growth curves, kept as a resource so the asset is never unloaded
+pub struct GrowthCurvesHandle(pub Handle<GrowthCurves>);
+
+// every bit of xp the player has gained this game
+#[derive(Component, Serialize, Deserialize, Clone, Copy, Default, Debug, PartialEq)]
+pub struct Experience(pub u32);
+
+// what the player gained from reaching a level, shown after the battle
+#[derive(Clone, Debug, PartialEq)]
+pub struct LevelUp {
+    pub level: u32,
+    pub max_health: i32,
+    pub attack: i32,
+    pub defense: i32,
+    pub speed: i32,
+}
+
+impl GrowthCurves {
+    // every level the player goes up going from one amount of xp to another
+    pub fn level_ups(&self, level: u32, xp: u32) -> Vec<LevelUp> {
+        (level + 1..=self.max_level)
+            .take_while(|&next| xp as i64 >= self.xp.total(next) as i64)
+            .map(|next| LevelUp {
+                level: next,
+                max_health: self.max_health.gain(next),
+                attack: self.attack.gain(next),
+                defense: self.defense.gain(next),
+                speed: self.speed.gain(next),
+            })
+            .collect()
+    }
+}
+
+// give the player xp and level them up for as long as it is enough, the health they gain
+// from a level up is healed straight away. returns every level up so it can be shown
+pub fn gain_xp(
+    curves: &GrowthCurves,
+    xp: u32,
+    experience: &mut Experience,
+    stats: &mut Stats,
+    base: &mut BaseStats
+) -> Vec<LevelUp> {
+    experience.0 = experience.0.saturating_add(xp);
+    let level_ups = curves.level_ups(stats.level, experience.0);
+
+    for level_up in level_ups.iter() {
+        base.max_health += level_up.max_health;
+        base.attack += level_up.attack;
+        base.defense += level_up.defense;
+        base.speed += level_up.speed;
+
+        // the rest of the stats are worked out again from the base stats
+        stats.level = level_up.level;
+        stats.max_health += level_up.max_health;
+        stats.health = (stats.health + level_up.max_health).min(stats.max_health);
+    }
+    level_ups
+}
+
+#[derive(Default)]
+struct GrowthCurvesLoader;
+
+impl AssetLoader for GrowthCurvesLoader {
+    fn load<'a>(
+        &'a self,
+        bytes: &'a [u8],
+        load_context: &'a mut LoadContext,
+    ) -> BoxedFuture<'a, Result<(), anyhow::Error>> {
+        Box::pin(async move {
+            let curves: GrowthCurves = ron::de::from_bytes(bytes)?;
+            load_context.set_default_asset(LoadedAsset::new(curves));
+            Ok(())
+        })
+    }
+
+    fn extensions(&self) -> &[&str] {
+        &["growth.ron"]
+    }
+}
+
+impl Plugin for LevelingPlugin {
+    fn build(&self, app: &mut App) {
+        app
+            .add_asset::<GrowthCurves>()
+            .init_asset_loader::<GrowthCurvesLoader>()
+            .add_startup_system(load_growth_curves);
+    }
+}
+
+fn load_growth_curves(mut commands: Commands, assets: Res<AssetServer>) {
+    commands.insert_resource(GrowthCurvesHandle(assets.load(GROWTH_FILE)));
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // the same numbers as player.growth.ron when these were written, kept here so
+    // changing the pace of the game does not change what is tested
+    const CURVES: &str = "(
+        max_level: 50,
+        xp: (per_level: 10.0, exponent: 1.5),
+        max_health: (per_level: 6.0),
+        attack: (per_level: 2.0),
+        defense: (per_level: 1.0, exponent: 1.1),
+        speed: (per_level: 0.5),
+    )";
+
+    fn curves() -> GrowthCurves {
+        ron::de::from_str(CURVES).unwrap()
+    }
+
+    fn level_1() -> (Stats, BaseStats) {
+        let stats = Stats { health: 40, max_health: 40, attack: 8, defense: 2, speed: 4, level: 1 };
+        let base = BaseStats::from_stats(&stats);
+        (stats, base)
+    }
+
+    #[test]
+    fn nothing_is_gained_at_level_1() {
+        let curves = curves();
+        for curve in [curves.xp, curves.max_health, curves.attack, curves.defense, curves.speed] {
+            assert_eq!(curve.total(1), 0);
+            assert_eq!(curve.gain(1), 0);
+            // level 0 is treated as level 1
+            assert_eq!(curve.total(0), 0);
+        }
+    }
+
+    #[test]
+    fn curves_grow_by_their_exponent() {
+        let curves = curves();
+        // 10 * 3 ^ 1.5 = 51.96
+        assert_eq!(curves.xp.total(4), 52);
+        // 10 * 2 ^ 1.5 = 28.28
+        assert_eq!(curves.xp.gain(4), 52 - 28);
+        // 10 ^ 1.1 = 12.59 and 9 ^ 1.1 = 11.21
+        assert_eq!(curves.defense.total(11), 13);
+        assert_eq!(curves.defense.gain(11), 2);
+        // the same every level when the exponent is left out
+        assert_eq!(curves.max_health.total(11), 60);
+        assert!((2..=50).all(|level| curves.attack.gain(level) == 2));
+    }
+
+    #[test]
+    fn stat_gains_at_a_known_level() {
+        let level_ups = curves().level_ups(3, 52);
+        assert_eq!(level_ups, vec![LevelUp { level: 4, max_health: 6, attack: 2, defense: 1, speed: 1 }]);
+    }
+
+    #[test]
+    fn halves_are_rounded_up() {
+        let curves = curves();
+        // 0.5 a level adds up to 1, 1, 2, 2, 3 from level 2, so the gains go 1, 0, 1, 0, 1
+        let gains: Vec<i32> = (2..=6).map(|level| curves.speed.gain(level)).collect();
+        assert_eq!(gains, vec![1, 0, 1, 0, 1]);
+    }
+
+    #[test]
+    fn large_xp_gives_several_level_ups() {
+        let curves = curves();
+        let levels = |xp| curves.level_ups(1, xp).iter().map(|level_up| level_up.level).collect::<Vec<_>>();
+        assert_eq!(levels(9), Vec::<u32>::new());
+        assert_eq!(levels(10), vec![2]);
+        // 80 is exactly what level 5 needs
+        assert_eq!(levels(79), vec![2, 3, 4]);
+        assert_eq!(levels(80), vec![2, 3, 4, 5]);
+    }
+
+    #[test]
+    fn levels_stop_at_the_max_level() {
+        let mut curves = curves();
+        curves.max_level = 3;
+        let level_ups = curves.level_ups(1, u32::MAX);
+        assert_eq!(level_ups.last().unwrap().level, 3);
+        assert!(curves.level_ups(3, u32::MAX).is_empty());
+    }
+
+    #[test]
+    fn every_gain_is_added_to_the_player() {
+        let curves = curves();
+        let (mut stats, mut base) = level_1();
+        stats.damage(10);
+        let mut experience = Experience(0);
+
+        // 112 is what level 6 needs, going up 5 levels at once
+        let level_ups = gain_xp(&curves, 112, &mut experience, &mut stats, &mut base);
+        assert_eq!(level_ups.len(), 5);
+        assert_eq!(experience, Experience(112));
+        assert_eq!(base, BaseStats { max_health: 70, attack: 18, defense: 8, speed: 7 });
+        // the health gained is healed but the damage is not
+        assert_eq!((stats.level, stats.health, stats.max_health), (6, 60, 70));
+
+        // xp is added up, 1 more is not enough for level 7
+        assert!(gain_xp(&curves, 1, &mut experience, &mut stats, &mut base).is_empty());
+        assert_eq!(experience, Experience(113));
+    }
+
+    #[test]
+    fn game_curves_can_be_read() {
+        let curves: GrowthCurves = ron::de::from_str(include_str!("../assets/data/player.growth.ron")).unwrap();
+        assert!(curves.max_level > 1);
+    }
+}
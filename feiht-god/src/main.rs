@@ -3,6 +3,7 @@
 use bevy::prelude::*;
 use bevy::render::camera::ScalingMode;
 use bevy::window::*;
+use rand::{rngs::StdRng, SeedableRng};
 
 pub const CLEAR: Color = Color::rgb(0.1, 0.1, 0.1);
 pub const RESOLUTION: f32 = 10.80 / 7.20;
@@ -12,8 +13,28 @@ mod player;
 mod debug;
 mod sprites;
 mod tilemap;
+mod map;
+mod tiled;
 mod combat;
 mod fadeout;
+mod gameover;
+mod stats;
+mod damage;
+mod collision;
+mod input;
+mod animation;
+mod text;
+mod dialogue;
+mod menu;
+mod main_menu;
+mod pause;
+mod save;
+mod enemies;
+mod items;
+mod equipment;
+mod leveling;
+mod sword;
+mod encounters;
 
 use player::PlayerPlugin;
 use debug::DebugPlugin;
@@ -21,19 +42,52 @@ use sprites::SpritePlugin;
 use tilemap::TileMapPlugin;
 use combat::CombatPlugin;
 use fadeout::FadeoutPlugin;
+use gameover::GameOverPlugin;
+use enemies::EnemyPlugin;
+use items::ItemPlugin;
+use equipment::EquipmentPlugin;
+use leveling::LevelingPlugin;
+use sword::SwordPlugin;
+use encounters::EncounterPlugin;
+use input::InputPlugin;
+use animation::AnimationPlugin;
+use text::TextPlugin;
+use dialogue::DialoguePlugin;
+use menu::MenuPlugin;
+use main_menu::MainMenuPlugin;
+use pause::PausePlugin;
+use save::SavePlugin;
 
 #[derive(Debug, Clone, PartialEq, Eq, Hash, Copy)]
 pub enum GameState {
+    MainMenu,
     Overworld,
     Combat,
+    GameOver,
+    // pushed on top of the overworld or combat, which carry on where they were once it is popped
+    Pause,
+}
+
+// every random roll in the game comes from this rng, setting the FEIHT_GOD_SEED
+// environment variable to a number will make the rolls the same every run
+pub struct GameRng(pub StdRng);
+
+impl GameRng {
+    fn from_env() -> Self {
+        match std::env::var("FEIHT_GOD_SEED").ok().and_then(|seed| seed.parse().ok()) {
+            Some(seed) => GameRng(StdRng::seed_from_u64(seed)),
+            None => GameRng(StdRng::from_entropy()),
+        }
+    }
 }
 
 fn main() {
     let height = 720.0;
 
     App::new()
-        .add_state(GameState::Overworld)
+        .add_state(GameState::MainMenu)
         .insert_resource(ClearColor(CLEAR))
+        .insert_resource(GameRng::from_env())
         .insert_resource(WindowDescriptor {
             width: height * RESOLUTION,
             height: height,
@@ -47,12 +101,27 @@ fn main() {
             ..Default::default()
         })
         .add_plugins(DefaultPlugins)
+        .add_plugin(InputPlugin)
         .add_plugin(PlayerPlugin)
         .add_plugin(DebugPlugin)
         .add_plugin(SpritePlugin)
+        .add_plugin(AnimationPlugin)
+        .add_plugin(TextPlugin)
         .add_plugin(TileMapPlugin)
         .add_plugin(CombatPlugin)
         .add_plugin(FadeoutPlugin)
+        .add_plugin(GameOverPlugin)
+        .add_plugin(EnemyPlugin)
+        .add_plugin(ItemPlugin)
+        .add_plugin(EquipmentPlugin)
+        .add_plugin(LevelingPlugin)
+        .add_plugin(SwordPlugin)
+        .add_plugin(EncounterPlugin)
+        .add_plugin(DialoguePlugin)
+        .add_plugin(MenuPlugin)
+        .add_plugin(MainMenuPlugin)
+        .add_plugin(PausePlugin)
+        .add_plugin(SavePlugin)
         .add_startup_system(spawn_camera)
         .run();
 }
@@ -65,8 +134,8 @@ fn spawn_camera(mut commands: Commands) {
     // setup normalized cordinate system
     camera.orthographic_projection.top = 1.0;
     camera.orthographic_projection.bottom = -1.0;
-    camera.orthographic_projection.right = 1.0 * RESOLUTION;
-    camera.orthographic_projection.left = -1.0 * RESOLUTION;
+    camera.orthographic_projection.right = RESOLUTION;
+    camera.orthographic_projection.left = -RESOLUTION;
 
     // Simple pixel art
     camera.orthographic_projection.scaling_mode = ScalingMode::None;
@@ -0,0 +1,252 @@
+// The title screen the game starts on, a game is started or loaded from here and the key
+// bindings can be changed before playing.
+
+use bevy::{prelude::*, app::AppExit};
+
+use crate::{GameState, sprites::SpriteSheet, fadeout::create_map_fadeout, tilemap::{ChangeMap, PlayerSpawn, WorldHandle}, map::WorldAsset, input::{InputMap, Rebinding}, menu::{Menu, MenuOption, spawn_menu, settings_options, pick_setting}, save::{LoadGame, SaveData, SAVE_SLOTS, read_slots, slot_text}, text::{BitmapText, TextAlign, spawn_text}};
+
+pub struct MainMenuPlugin;
+
+// sent when a new game is started, everything that carries over from a game that was quit
+// (like the stats of the player) is put back the way it is at the start of the game
+pub struct NewGame;
+
+// everything on the title screen is a child of this, which is a child of the camera
+#[derive(Component)]
+struct MainMenuScreen {
+    // set once a game is started so nothing can be picked during the fadeout
+    leaving: bool,
+    // read from the save slots when the title screen is shown
+    has_saves: bool,
+}
+
+// which list of options a menu on the title screen is
+#[derive(Component, Clone, Copy, PartialEq, Eq)]
+enum MainMenuPage {
+    Main,
+    Settings,
+    // the save slots to continue from
+    Load,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum MainMenuOption {
+    NewGame,
+    Continue,
+    Settings,
+    Quit,
+}
+
+impl MainMenuOption {
+    const ALL: [MainMenuOption; 4] = [
+        MainMenuOption::NewGame,
+        MainMenuOption::Continue,
+        MainMenuOption::Settings,
+        MainMenuOption::Quit,
+    ];
+
+    fn text(self) -> &'static str {
+        match self {
+            MainMenuOption::NewGame => "New Game",
+            MainMenuOption::Continue => "Continue",
+            MainMenuOption::Settings => "Settings",
+            MainMenuOption::Quit => "Quit",
+        }
+    }
+}
+
+const TITLE_SIZE: f32 = 0.2;
+const OPTION_SIZE: f32 = 0.08;
+
+impl Plugin for MainMenuPlugin {
+    fn build(&self, app: &mut App) {
+        app
+            .add_event::<NewGame>()
+            .add_system_set(SystemSet::on_enter(GameState::MainMenu).with_system(spawn_main_menu))
+            .add_system_set(SystemSet::on_update(GameState::MainMenu).with_system(main_menu.after("menus")))
+            .add_system_set(SystemSet::on_exit(GameState::MainMenu).with_system(despawn_main_menu));
+    }
+}
+
+// the options of the first page, the ones that can not be picked yet are disabled
+fn main_options(world_loaded: bool, screen: &MainMenuScreen) -> Vec<MenuOption> {
+    MainMenuOption::ALL
+        .iter()
+        .map(|&option| {
+            let enabled = match option {
+                // the world file has the map a new game starts on, and the maps a save can be on
+                MainMenuOption::NewGame => world_loaded,
+                MainMenuOption::Continue => world_loaded && screen.has_saves,
+                MainMenuOption::Settings | MainMenuOption::Quit => true,
+            };
+            MenuOption::new(option.text(), enabled && !screen.leaving)
+        })
+        .collect()
+}
+
+fn spawn_page(commands: &mut Commands, screen: Entity, page: MainMenuPage, options: Vec<MenuOption>) {
+    // the top left of the options, the settings are wider so they start further left
+    let translation = match page {
+        MainMenuPage::Main => Vec3::new(-0.4, 0.1, 0.1),
+        MainMenuPage::Settings | MainMenuPage::Load => Vec3::new(-1.0, 0.3, 0.1),
+    };
+    let menu = spawn_menu(commands, GameState::MainMenu, options, OPTION_SIZE, translation);
+    commands.entity(menu).insert(page);
+    commands.entity(screen).add_child(menu);
+}
+
+// the save slots, only the ones with a save in them can be picked
+fn load_options() -> Vec<MenuOption> {
+    read_slots()
+        .iter()
+        .enumerate()
+        .map(|(slot, save)| MenuOption::new(&slot_text(slot, save), matches!(save, Ok(Some(_)))))
+        .chain(std::iter::once(MenuOption::new("Back", true)))
+        .collect()
+}
+
+fn back_to_main(commands: &mut Commands, screen: Entity, menu: Entity, world_loaded: bool, main_menu_screen: &MainMenuScreen) {
+    commands.entity(menu).despawn_recursive();
+    spawn_page(commands, screen, MainMenuPage::Main, main_options(world_loaded, main_menu_screen));
+}
+
+fn spawn_main_menu(
+    mut commands: Commands,
+    // the title screen is put in front of the camera
+    camera_query: Query<Entity, With<Camera>>
+) {
+    let main_menu_screen = MainMenuScreen {
+        leaving: false,
+        has_saves: read_slots().iter().any(|save| matches!(save, Ok(Some(_)))),
+    };
+    let options = main_options(false, &main_menu_screen);
+    let screen = commands
+        .spawn()
+        .insert(main_menu_screen)
+        .insert(Name::new("Main Menu"))
+        // in front of the map but behind the fadeout
+        .insert(Transform::from_xyz(0.0, 0.0, -2.0))
+        .insert(GlobalTransform::default())
+        .id();
+
+    let title = spawn_text(
+        &mut commands,
+        BitmapText {
+            text: String::from("Feiht God"),
+            size: TITLE_SIZE,
+            color: Color::WHITE,
+            align: TextAlign::Center,
+            max_width: None,
+        },
+        Vec3::new(0.0, 0.6, 0.1)
+    );
+    commands.entity(screen).add_child(title);
+    spawn_page(&mut commands, screen, MainMenuPage::Main, options);
+
+    if let Some(camera) = camera_query.iter().next() {
+        commands.entity(camera).add_child(screen);
+    }
+}
+
+fn despawn_main_menu(mut commands: Commands, screen_query: Query<Entity, With<MainMenuScreen>>) {
+    for entity in screen_query.iter() {
+        commands.entity(entity).despawn_recursive();
+    }
+}
+
+#[allow(clippy::too_many_arguments)]
+fn main_menu(
+    // commands are used to switch pages and to spawn the fadeout
+    mut commands: Commands,
+    mut screen_query: Query<(Entity, &mut MainMenuScreen)>,
+    mut menu_query: Query<(Entity, &mut Menu, &MainMenuPage)>,
+    // a new game starts at the start of the world file
+    world_handle: Res<WorldHandle>,
+    worlds: Res<Assets<WorldAsset>>,
+    mut new_game_events: EventWriter<NewGame>,
+    mut load_game_events: EventWriter<LoadGame>,
+    // the settings page shows and changes the key bindings
+    mut input_map: ResMut<InputMap>,
+    mut rebinding: ResMut<Rebinding>,
+    mut exit: EventWriter<AppExit>,
+    sprite_sheet: Res<SpriteSheet>
+) {
+    let (screen, mut main_menu_screen) = match screen_query.get_single_mut() {
+        Ok(screen) => screen,
+        Err(_) => return,
+    };
+    let (menu_entity, mut menu, &page) = match menu_query.get_single_mut() {
+        Ok(menu) => menu,
+        Err(_) => return,
+    };
+    let world = worlds.get(&world_handle.0);
+
+    match page {
+        MainMenuPage::Main => {
+            menu.options = main_options(world.is_some(), &main_menu_screen);
+
+            match menu.picked.map(|picked| MainMenuOption::ALL[picked]) {
+                Some(MainMenuOption::NewGame) => {
+                    // the new game option is only enabled once the world has loaded
+                    let world = world.unwrap();
+                    new_game_events.send(NewGame);
+                    main_menu_screen.leaving = true;
+                    create_map_fadeout(&mut commands, GameState::Overworld, ChangeMap {
+                        map: world.start.map.clone(),
+                        spawn: PlayerSpawn::Point(world.start.spawn_point.clone()),
+                    }, &sprite_sheet);
+                }
+                Some(MainMenuOption::Settings) => {
+                    commands.entity(menu_entity).despawn_recursive();
+                    spawn_page(&mut commands, screen, MainMenuPage::Settings, settings_options(&input_map, &rebinding));
+                }
+                Some(MainMenuOption::Continue) => {
+                    commands.entity(menu_entity).despawn_recursive();
+                    spawn_page(&mut commands, screen, MainMenuPage::Load, load_options());
+                }
+                Some(MainMenuOption::Quit) => exit.send(AppExit),
+                None => (),
+            }
+        }
+        MainMenuPage::Load => {
+            if main_menu_screen.leaving { return; }
+
+            match menu.picked {
+                Some(slot) if slot < SAVE_SLOTS => {
+                    // the slot was read again in case it changed since the page was shown
+                    match SaveData::read(slot) {
+                        Ok(Some(save)) => {
+                            main_menu_screen.leaving = true;
+                            for option in menu.options.iter_mut() {
+                                option.enabled = false;
+                            }
+                            create_map_fadeout(&mut commands, GameState::Overworld, ChangeMap {
+                                map: save.map.clone(),
+                                spawn: PlayerSpawn::Position(Vec2::new(save.position.0, save.position.1)),
+                            }, &sprite_sheet);
+                            load_game_events.send(LoadGame(save));
+                        }
+                        Ok(None) => warn!("Slot {} is empty", slot + 1),
+                        Err(error) => warn!("Can not load slot {}: {}", slot + 1, error),
+                    }
+                }
+                // Back
+                Some(_) => back_to_main(&mut commands, screen, menu_entity, world.is_some(), &main_menu_screen),
+                None if menu.cancelled => back_to_main(&mut commands, screen, menu_entity, world.is_some(), &main_menu_screen),
+                None => (),
+            }
+        }
+        MainMenuPage::Settings => {
+            // the keys change when an action is rebound
+            menu.options = settings_options(&input_map, &rebinding);
+
+            let back = match menu.picked {
+                Some(picked) => pick_setting(picked, &mut input_map, &mut rebinding),
+                None => menu.cancelled,
+            };
+            if back {
+                back_to_main(&mut commands, screen, menu_entity, world.is_some(), &main_menu_screen);
+            }
+        }
+    }
+}
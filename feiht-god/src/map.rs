@@ -0,0 +1,457 @@
+// The map file format, a map is a RON file ending in .map.ron that is loaded through
+// the AssetServer like any other asset, see assets/maps/overworld.map.ron for an example.
+// The symbols in the layers of a map are looked up in a legend file (.legend.ron) that
+// the map points to, see assets/maps/tiles.legend.ron.
+// Maps made in Tiled are turned into the same MapAsset by the loader in tiled.rs.
+// Every map is given a name in the world file (.world.ron) which is what warps refer to,
+// see assets/maps/main.world.ron
+
+use std::{fmt, path::Path};
+
+use bevy::{asset::{AssetLoader, LoadContext, LoadedAsset, BoxedFuture}, reflect::TypeUuid, utils::HashMap};
+use serde::Deserialize;
+
+// a space in a layer is always an empty cell, so it can not be used in the legend
+pub const EMPTY_TILE: char = ' ';
+
+#[derive(Deserialize, Clone, Debug, Default)]
+pub struct MapMetadata {
+    // the name shown to the player
+    pub name: String,
+}
+
+// what a symbol in a layer turns into
+#[derive(Deserialize, Clone, Debug, Default)]
+#[serde(deny_unknown_fields)]
+pub struct TileDefinition {
+    // index of the tile sprite in Sprites.png
+    pub sprite: usize,
+    // the player can not walk through colliders
+    #[serde(default)]
+    pub collider: bool,
+    // walking over this tile can start an encounter from the given zone
+    #[serde(default)]
+    pub encounter_zone: Option<String>,
+    // anything else that gameplay code wants to know about the tile
+    #[serde(default)]
+    pub properties: HashMap<String, String>,
+}
+
+// every symbol that can be used in the layers of a map
+#[derive(Deserialize, Clone, Debug)]
+#[serde(deny_unknown_fields)]
+pub struct TileLegend {
+    pub tiles: HashMap<char, TileDefinition>,
+}
+
+impl TileLegend {
+    pub fn from_bytes(bytes: &[u8]) -> Result<Self, MapError> {
+        let legend: TileLegend = ron::de::from_bytes(bytes).map_err(MapError::from)?;
+
+        if legend.tiles.contains_key(&EMPTY_TILE) {
+            return Err(MapError::Invalid(String::from(
+                "a space can not be used as a symbol, it is always an empty cell"
+            )));
+        }
+
+        Ok(legend)
+    }
+}
+
+#[derive(Deserialize, Clone, Debug)]
+pub enum MapObjectKind {
+    // stepping on a warp moves the player to a spawn point of another map
+    Warp { map: String, spawn_point: String },
+    // a character standing on the map, talking to them starts the dialogue file
+    // (relative to the assets folder), npcs without one have nothing to say
+    Npc {
+        sprite: usize,
+        #[serde(default)]
+        dialogue: Option<String>,
+    },
+    // an enemy standing on the map that is fought with the sword instead of in combat,
+    // its stats come from the EnemyDatabase entry with the id
+    Enemy {
+        sprite: usize,
+        enemy: String,
+    },
+}
+
+// something placed on the map that is not a tile
+#[derive(Deserialize, Clone, Debug)]
+pub struct MapObject {
+    pub name: String,
+    pub kind: MapObjectKind,
+    // in tiles from the top left of the map
+    pub position: (i32, i32),
+}
+
+// what a layer is used for, this decides how (and if) the layer is drawn
+#[derive(Deserialize, Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum LayerKind {
+    // the floor, drawn below everything else
+    #[default]
+    Ground,
+    // drawn above the ground but below the player
+    Decoration,
+    // never drawn, every tile in the layer blocks the player
+    Collision,
+    // drawn above the player, for trees and roofs the player walks behind
+    Overhead,
+}
+
+#[derive(Clone, Debug)]
+pub struct MapLayer {
+    pub name: String,
+    pub kind: LayerKind,
+    pub width: usize,
+    // row by row from the top left, every cell is an index into MapAsset::tiles or None when empty
+    pub cells: Vec<Option<usize>>,
+}
+
+impl MapLayer {
+    // iterate over every cell that has a tile, with the x and y of the cell in tiles
+    pub fn iter_tiles(&self) -> impl Iterator<Item = (usize, usize, usize)> + '_ {
+        self.cells
+            .iter()
+            .enumerate()
+            .filter_map(move |(i, cell)| cell.map(|tile| (i % self.width, i / self.width, tile)))
+    }
+}
+
+#[derive(TypeUuid, Clone, Debug)]
+#[uuid = "8e2f6b1d-4c7a-4d3e-b5a9-1f0c7e2d9a46"]
+pub struct MapAsset {
+    pub metadata: MapMetadata,
+    // every kind of tile used by the layers
+    pub tiles: Vec<TileDefinition>,
+    // layers are drawn in order, later layers are drawn on top of earlier ones
+    pub layers: Vec<MapLayer>,
+    // named places the player can be put, in tiles from the top left of the map
+    pub spawn_points: HashMap<String, (i32, i32)>,
+    pub objects: Vec<MapObject>,
+}
+
+// a layer as it is written in a .map.ron file
+#[derive(Deserialize)]
+struct MapFileLayer {
+    name: String,
+    // Ground when it is left out
+    #[serde(default)]
+    kind: LayerKind,
+    // every string is one row of tiles from top to bottom, every
+    // character is one tile that is looked up in the legend
+    rows: Vec<String>,
+}
+
+// a map as it is written in a .map.ron file
+#[derive(Deserialize)]
+struct MapFile {
+    #[serde(default)]
+    metadata: MapMetadata,
+    // path of the legend file, relative to the map
+    legend: String,
+    layers: Vec<MapFileLayer>,
+    #[serde(default)]
+    spawn_points: HashMap<String, (i32, i32)>,
+    #[serde(default)]
+    objects: Vec<MapObject>,
+}
+
+#[derive(Debug)]
+pub enum MapError {
+    // the file is not valid RON or does not match the map format
+    Parse {
+        line: usize,
+        column: usize,
+        message: String,
+    },
+    // the file is valid but something in it does not make sense
+    Invalid(String),
+    // symbols used in a layer that are not in the legend, only the first use of each symbol is listed
+    UnknownSymbols(Vec<UnknownSymbol>),
+}
+
+#[derive(Debug)]
+pub struct UnknownSymbol {
+    pub symbol: char,
+    pub layer: String,
+    // row and column in the layer, starting at 1 like the lines of a text editor
+    pub row: usize,
+    pub column: usize,
+}
+
+impl From<ron::Error> for MapError {
+    fn from(error: ron::Error) -> Self {
+        MapError::Parse {
+            line: error.position.line,
+            column: error.position.col,
+            message: error.code.to_string(),
+        }
+    }
+}
+
+impl fmt::Display for MapError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            // some errors (like unknown fields) are not given a position by ron
+            MapError::Parse { line: 0, message, .. } => write!(f, "{}", message),
+            MapError::Parse { line, column, message } => {
+                write!(f, "line {}, column {}: {}", line, column, message)
+            }
+            MapError::Invalid(message) => write!(f, "{}", message),
+            MapError::UnknownSymbols(symbols) => {
+                write!(f, "symbols that are not in the legend:")?;
+                for unknown in symbols.iter() {
+                    write!(
+                        f, " '{}' (layer \"{}\", row {}, column {})",
+                        unknown.symbol, unknown.layer, unknown.row, unknown.column
+                    )?;
+                }
+                Ok(())
+            }
+        }
+    }
+}
+
+impl std::error::Error for MapError {}
+
+impl MapFile {
+    fn from_bytes(bytes: &[u8]) -> Result<Self, MapError> {
+        Ok(ron::de::from_bytes(bytes)?)
+    }
+
+    // look up every symbol of every layer in the legend
+    fn into_map_asset(self, legend: TileLegend) -> Result<MapAsset, MapError> {
+        // give every symbol of the legend an index into the tile list
+        let mut tiles = Vec::new();
+        let mut symbols = HashMap::default();
+        for (symbol, definition) in legend.tiles {
+            symbols.insert(symbol, tiles.len());
+            tiles.push(definition);
+        }
+
+        let mut unknown_symbols: Vec<UnknownSymbol> = Vec::new();
+        let mut layers = Vec::new();
+
+        for layer in self.layers {
+            let width = layer.rows.iter().map(|row| row.chars().count()).max().unwrap_or(0);
+            let height = layer.rows.len();
+
+            // rows shorter than the widest row are filled up with empty cells
+            let mut cells = vec![None; width * height];
+            for (y, row) in layer.rows.iter().enumerate() {
+                for (x, symbol) in row.chars().enumerate() {
+                    if symbol == EMPTY_TILE { continue; }
+
+                    match symbols.get(&symbol) {
+                        Some(&index) => cells[y * width + x] = Some(index),
+                        None => {
+                            if !unknown_symbols.iter().any(|unknown| unknown.symbol == symbol) {
+                                unknown_symbols.push(UnknownSymbol {
+                                    symbol,
+                                    layer: layer.name.clone(),
+                                    row: y + 1,
+                                    column: x + 1,
+                                });
+                            }
+                        }
+                    }
+                }
+            }
+
+            layers.push(MapLayer { name: layer.name, kind: layer.kind, width, cells });
+        }
+
+        if !unknown_symbols.is_empty() {
+            return Err(MapError::UnknownSymbols(unknown_symbols));
+        }
+
+        Ok(MapAsset {
+            metadata: self.metadata,
+            tiles,
+            layers,
+            spawn_points: self.spawn_points,
+            objects: self.objects,
+        })
+    }
+}
+
+#[derive(Default)]
+pub struct MapLoader;
+
+impl AssetLoader for MapLoader {
+    fn load<'a>(
+        &'a self,
+        bytes: &'a [u8],
+        load_context: &'a mut LoadContext,
+    ) -> BoxedFuture<'a, Result<(), anyhow::Error>> {
+        Box::pin(async move {
+            let path = load_context.path().to_path_buf();
+            // put the path in front of the error so it is clear which file is broken
+            let in_file = |path: &Path, error: MapError| anyhow::anyhow!("{}, {}", path.display(), error);
+
+            let file = MapFile::from_bytes(bytes).map_err(|error| in_file(&path, error))?;
+
+            // the legend is stored next to the map
+            let legend_path = path.parent().unwrap_or_else(|| Path::new("")).join(&file.legend);
+            let legend_bytes = load_context.read_asset_bytes(&legend_path).await?;
+            let legend = TileLegend::from_bytes(&legend_bytes).map_err(|error| in_file(&legend_path, error))?;
+
+            let map = file.into_map_asset(legend).map_err(|error| in_file(&path, error))?;
+            load_context.set_default_asset(LoadedAsset::new(map));
+            Ok(())
+        })
+    }
+
+    fn extensions(&self) -> &[&str] {
+        &["map.ron"]
+    }
+}
+
+// where the player starts a new game
+#[derive(Deserialize, Clone, Debug)]
+pub struct WorldStart {
+    pub map: String,
+    pub spawn_point: String,
+}
+
+// every map of the game by name
+#[derive(Deserialize, TypeUuid, Clone, Debug)]
+#[uuid = "0b7d3e52-91c4-4a8f-8d26-6e5a1c3f7b09"]
+#[serde(deny_unknown_fields)]
+pub struct WorldAsset {
+    pub start: WorldStart,
+    // the path of every map relative to the assets folder
+    pub maps: HashMap<String, String>,
+}
+
+#[derive(Default)]
+pub struct WorldLoader;
+
+impl AssetLoader for WorldLoader {
+    fn load<'a>(
+        &'a self,
+        bytes: &'a [u8],
+        load_context: &'a mut LoadContext,
+    ) -> BoxedFuture<'a, Result<(), anyhow::Error>> {
+        Box::pin(async move {
+            let world: WorldAsset = ron::de::from_bytes(bytes).map_err(|error| {
+                anyhow::anyhow!("{}, {}", load_context.path().display(), MapError::from(error))
+            })?;
+            load_context.set_default_asset(LoadedAsset::new(world));
+            Ok(())
+        })
+    }
+
+    fn extensions(&self) -> &[&str] {
+        &["world.ron"]
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const LEGEND: &str = "(tiles: {'w': (sprite: 97, collider: true), '.': (sprite: 0)})";
+
+    fn load_map(text: &str) -> Result<MapAsset, MapError> {
+        MapFile::from_bytes(text.as_bytes())?.into_map_asset(TileLegend::from_bytes(LEGEND.as_bytes())?)
+    }
+
+    #[test]
+    fn map_is_read_from_its_rows() {
+        let map = load_map(r#"(
+            metadata: (name: "Test"),
+            legend: "test.legend.ron",
+            layers: [
+                (name: "ground", rows: ["www", "w.", "w w"]),
+                (name: "walls", kind: Collision, rows: ["w"]),
+            ],
+            spawn_points: {"start": (1, 1)},
+        )"#).unwrap();
+
+        assert_eq!(map.metadata.name, "Test");
+        assert_eq!(map.spawn_points["start"], (1, 1));
+        assert!(map.objects.is_empty());
+        assert_eq!(map.layers.len(), 2);
+        assert_eq!(map.layers[0].kind, LayerKind::Ground);
+        assert_eq!(map.layers[1].kind, LayerKind::Collision);
+
+        let ground = &map.layers[0];
+        assert_eq!(ground.width, 3);
+        // the short row is filled up with an empty cell and the space is left empty
+        let sprites: Vec<Option<usize>> = ground.cells.iter().map(|cell| cell.map(|tile| map.tiles[tile].sprite)).collect();
+        assert_eq!(sprites, vec![
+            Some(97), Some(97), Some(97),
+            Some(97), Some(0), None,
+            Some(97), None, Some(97),
+        ]);
+        assert_eq!(ground.iter_tiles().count(), 7);
+    }
+
+    #[test]
+    fn parse_errors_have_the_line_and_column() {
+        let text = "(\n    legend: \"test.legend.ron\",\n    layers: [(name: \"ground\", rows: [\"ww\", 5])],\n)";
+        match load_map(text) {
+            Err(MapError::Parse { line, column, .. }) => {
+                assert_eq!(line, 3);
+                // ron points at the 5 that should have been a string
+                assert_eq!(column, text.lines().nth(2).unwrap().find('5').unwrap() + 1);
+            }
+            other => panic!("expected a parse error, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn malformed_rows_are_rejected() {
+        // a row that is not closed
+        assert!(matches!(
+            load_map("(legend: \"test.legend.ron\", layers: [(name: \"ground\", rows: [\"ww])])"),
+            Err(MapError::Parse { .. })
+        ));
+        // rows written as a single string instead of a list of them
+        assert!(matches!(
+            load_map("(legend: \"test.legend.ron\", layers: [(name: \"ground\", rows: \"ww\")])"),
+            Err(MapError::Parse { .. })
+        ));
+    }
+
+    #[test]
+    fn symbols_missing_from_the_legend_are_listed() {
+        let text = "(legend: \"test.legend.ron\", layers: [(name: \"ground\", rows: [\"w.\", \".xw\", \"x?\"])])";
+        match load_map(text) {
+            Err(MapError::UnknownSymbols(symbols)) => {
+                // only the first use of every symbol, with its layer, row and column
+                let found: Vec<(char, &str, usize, usize)> = symbols
+                    .iter()
+                    .map(|unknown| (unknown.symbol, unknown.layer.as_str(), unknown.row, unknown.column))
+                    .collect();
+                assert_eq!(found, vec![('x', "ground", 2, 2), ('?', "ground", 3, 2)]);
+            }
+            other => panic!("expected unknown symbols, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn legend_can_not_use_a_space() {
+        assert!(TileLegend::from_bytes(LEGEND.as_bytes()).is_ok());
+        assert!(matches!(
+            TileLegend::from_bytes(b"(tiles: {' ': (sprite: 0), 'w': (sprite: 97)})"),
+            Err(MapError::Invalid(_))
+        ));
+    }
+
+    #[test]
+    fn legend_rejects_unknown_fields() {
+        assert!(matches!(
+            TileLegend::from_bytes(b"(tiles: {'w': (sprite: 97, colider: true)})"),
+            Err(MapError::Parse { .. })
+        ));
+    }
+
+    #[test]
+    fn game_legend_is_valid() {
+        assert!(TileLegend::from_bytes(include_bytes!("../assets/maps/tiles.legend.ron")).is_ok());
+    }
+}
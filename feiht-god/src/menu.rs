@@ -0,0 +1,292 @@
+// Lists of options like the ones on the title screen, moved through with the move actions
+// and picked with confirm (so they work the same on a keyboard and a gamepad). Whatever spawned
+// a menu reads what was picked from the Menu component in a system that runs after "menus".
+
+use bevy::prelude::*;
+
+use crate::{GameState, input::{ActionState, InputAction, InputMap, Rebinding}, text::{BitmapText, TextAlign, spawn_text}};
+
+pub struct MenuPlugin;
+
+pub struct MenuOption {
+    pub text: String,
+    // disabled options are greyed out and skipped over
+    pub enabled: bool,
+}
+
+impl MenuOption {
+    pub fn new(text: &str, enabled: bool) -> Self {
+        MenuOption { text: text.to_string(), enabled }
+    }
+}
+
+#[derive(Component)]
+pub struct Menu {
+    pub options: Vec<MenuOption>,
+    pub selected: usize,
+    // the option confirm was pressed on this frame
+    pub picked: Option<usize>,
+    // cancel was pressed this frame
+    pub cancelled: bool,
+    // one text for every option, the children of the menu
+    texts: Vec<Entity>,
+    // the size of the text, for the texts of options added after the menu was spawned
+    size: f32,
+    // the menu is only moved through while this is the current state, so a menu left open
+    // under another state (like the item menu of a battle under the pause menu) waits for it
+    state: GameState,
+}
+
+// the options are spaced this fraction of the text size apart
+const OPTION_SPACING: f32 = 1.5;
+
+impl Plugin for MenuPlugin {
+    fn build(&self, app: &mut App) {
+        app
+            .add_system(navigate_menus.label("menus"))
+            .add_system(update_menu_text.after("menus"));
+    }
+}
+
+// spawn a menu for the state with the first option at the translation and the rest below it,
+// the menu starts on the first option that is enabled
+pub fn spawn_menu(commands: &mut Commands, state: GameState, options: Vec<MenuOption>, size: f32, translation: Vec3) -> Entity {
+    let texts: Vec<Entity> = (0..options.len())
+        .map(|i| spawn_option_text(commands, i, size))
+        .collect();
+    let selected = options.iter().position(|option| option.enabled).unwrap_or(0);
+
+    commands
+        .spawn()
+        .insert(Menu { options, selected, picked: None, cancelled: false, texts: texts.clone(), size, state })
+        .insert(Name::new("Menu"))
+        .insert(Transform::from_translation(translation))
+        .insert(GlobalTransform::default())
+        .push_children(&texts)
+        .id()
+}
+
+// the (empty) text of the option at the index, it is filled in by update_menu_text
+fn spawn_option_text(commands: &mut Commands, index: usize, size: f32) -> Entity {
+    spawn_text(
+        commands,
+        BitmapText {
+            text: String::new(),
+            size,
+            color: Color::WHITE,
+            align: TextAlign::Left,
+            max_width: None,
+        },
+        Vec3::new(0.0, -(index as f32) * size * OPTION_SPACING, 0.0)
+    )
+}
+
+// the options of a settings page, one for every action showing the keys it is bound to, then Reset and Back
+pub fn settings_options(input_map: &InputMap, rebinding: &Rebinding) -> Vec<MenuOption> {
+    InputAction::ALL
+        .iter()
+        .map(|&action| {
+            let keys = if rebinding.0 == Some(action) {
+                String::from("press a key, button stops")
+            } else {
+                input_map.keys(action).iter().map(|key| format!("{:?}", key)).collect::<Vec<_>>().join(", ")
+            };
+            MenuOption::new(&format!("{:<10}{}", format!("{:?}", action), keys), true)
+        })
+        .chain([MenuOption::new("Reset", true), MenuOption::new("Back", true)])
+        .collect()
+}
+
+// do what the picked option of a settings page does, true if it was Back
+pub fn pick_setting(picked: usize, input_map: &mut InputMap, rebinding: &mut Rebinding) -> bool {
+    match InputAction::ALL.get(picked) {
+        Some(&action) => {
+            rebinding.0 = Some(action);
+            false
+        }
+        // Reset is right after the actions
+        None if picked == InputAction::ALL.len() => {
+            input_map.reset();
+            false
+        }
+        None => true,
+    }
+}
+
+fn navigate_menus(
+    mut menu_query: Query<&mut Menu>,
+    mut actions: ResMut<ActionState>,
+    // the menus wait while a key is being bound so the key does not also move them
+    rebinding: Res<Rebinding>,
+    state: Res<State<GameState>>
+) {
+    for mut menu in menu_query.iter_mut() {
+        menu.picked = None;
+        menu.cancelled = false;
+        if menu.state != *state.current() || rebinding.0.is_some() || menu.options.is_empty() { continue; }
+
+        let count = menu.options.len();
+        // the options could have been replaced with fewer of them
+        if menu.selected >= count {
+            menu.selected = count - 1;
+        }
+        // move one option at a time in the direction pressed until an option that is
+        // enabled is found, this also moves off an option that was just disabled
+        let step = if actions.just_pressed(InputAction::MoveUp) { count - 1 } else { 1 };
+        let moved = actions.just_pressed(InputAction::MoveUp) || actions.just_pressed(InputAction::MoveDown);
+        if moved || !menu.options[menu.selected].enabled {
+            let mut selected = menu.selected;
+            for _ in 0..count {
+                selected = (selected + step) % count;
+                if menu.options[selected].enabled { break; }
+            }
+            menu.selected = selected;
+        }
+
+        if actions.just_pressed(InputAction::Confirm) && menu.options[menu.selected].enabled {
+            menu.picked = Some(menu.selected);
+            actions.clear();
+        } else if actions.just_pressed(InputAction::Cancel) {
+            menu.cancelled = true;
+            actions.clear();
+        }
+    }
+}
+
+fn update_menu_text(
+    // commands are used to add and remove texts when the number of options changes
+    mut commands: Commands,
+    mut menu_query: Query<(Entity, &mut Menu), Changed<Menu>>,
+    mut text_query: Query<&mut BitmapText>
+) {
+    for (entity, mut menu) in menu_query.iter_mut() {
+        // the options can be replaced with a shorter or longer list (like when a stack of items runs out)
+        let count = menu.options.len();
+        while menu.texts.len() > count {
+            if let Some(text) = menu.texts.pop() {
+                commands.entity(text).despawn_recursive();
+            }
+        }
+        // the new texts are filled in next frame, pushing them changes the menu so this runs again
+        while menu.texts.len() < count {
+            let text = spawn_option_text(&mut commands, menu.texts.len(), menu.size);
+            commands.entity(entity).add_child(text);
+            menu.texts.push(text);
+        }
+
+        for (i, (option, &entity)) in menu.options.iter().zip(menu.texts.iter()).enumerate() {
+            if let Ok(mut text) = text_query.get_mut(entity) {
+                let line = format!("{} {}", if i == menu.selected { ">" } else { " " }, option.text);
+                let color = if option.enabled { Color::WHITE } else { Color::GRAY };
+                // only change the text when it changes so it is not laid out again every frame
+                if text.text != line || text.color != color {
+                    text.text = line;
+                    text.color = color;
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use bevy::{ecs::{event::Events, system::CommandQueue}, input::{InputPlugin as BevyInputPlugin, keyboard::KeyboardInput, ElementState}};
+
+    use super::*;
+    use crate::input::InputPlugin;
+
+    fn options(count: usize) -> Vec<MenuOption> {
+        (0..count).map(|i| MenuOption::new(&format!("Option {}", i), true)).collect()
+    }
+
+    // a headless app with a menu of options in it
+    fn menu_app(count: usize) -> (App, Entity) {
+        let mut app = App::new();
+        app
+            .add_plugins(MinimalPlugins)
+            .init_resource::<ActionState>()
+            .init_resource::<Rebinding>()
+            .add_state(GameState::MainMenu)
+            .add_plugin(MenuPlugin);
+
+        let menu = add_menu(&mut app, GameState::MainMenu, count);
+        app.update();
+        (app, menu)
+    }
+
+    fn add_menu(app: &mut App, state: GameState, count: usize) -> Entity {
+        let mut queue = CommandQueue::default();
+        let menu = spawn_menu(&mut Commands::new(&mut queue, &app.world), state, options(count), 0.1, Vec3::ZERO);
+        queue.apply(&mut app.world);
+        menu
+    }
+
+    // press the first key bound to the action for a frame
+    fn press(app: &mut App, action: InputAction) {
+        let key_code = app.world.resource::<InputMap>().keys(action)[0];
+        for state in [ElementState::Pressed, ElementState::Released] {
+            app.world.resource_mut::<Events<KeyboardInput>>().send(KeyboardInput { scan_code: 0, key_code: Some(key_code), state });
+            app.update();
+        }
+    }
+
+    // the text of every option shown, and how many texts the menu has as children
+    fn shown(app: &App, menu: Entity) -> (Vec<String>, usize) {
+        let texts = app.world.get::<Menu>(menu).unwrap().texts
+            .iter()
+            .map(|&text| app.world.get::<BitmapText>(text).unwrap().text.clone())
+            .collect();
+        (texts, app.world.get::<Children>(menu).unwrap().len())
+    }
+
+    #[test]
+    fn every_option_is_shown() {
+        let (app, menu) = menu_app(3);
+        assert_eq!(shown(&app, menu), (vec![String::from("> Option 0"), String::from("  Option 1"), String::from("  Option 2")], 3));
+    }
+
+    #[test]
+    fn texts_follow_the_number_of_options() {
+        let (mut app, menu) = menu_app(3);
+        app.world.get_mut::<Menu>(menu).unwrap().selected = 2;
+        app.world.get_mut::<Menu>(menu).unwrap().options = options(1);
+        app.update();
+        app.update();
+        assert_eq!(shown(&app, menu), (vec![String::from("> Option 0")], 1));
+
+        app.world.get_mut::<Menu>(menu).unwrap().options = options(2);
+        app.update();
+        app.update();
+        assert_eq!(shown(&app, menu), (vec![String::from("> Option 0"), String::from("  Option 1")], 2));
+    }
+
+    #[test]
+    fn only_the_menus_of_the_current_state_are_moved() {
+        let mut app = App::new();
+        app
+            .add_plugins(MinimalPlugins)
+            .add_plugin(BevyInputPlugin)
+            .add_plugin(InputPlugin)
+            // the bindings saved on this machine are not used
+            .insert_resource(InputMap::default())
+            .add_state(GameState::Combat)
+            .add_plugin(MenuPlugin);
+        // the item menu of a battle is still open when the game is paused
+        let items = add_menu(&mut app, GameState::Combat, 3);
+        let pause = add_menu(&mut app, GameState::Pause, 3);
+        app.update();
+        app.world.resource_mut::<State<GameState>>().push(GameState::Pause).unwrap();
+        app.update();
+
+        press(&mut app, InputAction::MoveDown);
+        assert_eq!(app.world.get::<Menu>(pause).unwrap().selected, 1);
+        assert_eq!(app.world.get::<Menu>(items).unwrap().selected, 0);
+
+        // confirm goes to the pause menu however the menus are ordered
+        let key_code = app.world.resource::<InputMap>().keys(InputAction::Confirm)[0];
+        app.world.resource_mut::<Events<KeyboardInput>>().send(KeyboardInput { scan_code: 0, key_code: Some(key_code), state: ElementState::Pressed });
+        app.update();
+        assert_eq!(app.world.get::<Menu>(pause).unwrap().picked, Some(1));
+        assert_eq!(app.world.get::<Menu>(items).unwrap().picked, None);
+    }
+}
@@ -0,0 +1,356 @@
+// The pause menu. Pause is pushed on top of the overworld or combat instead of replacing
+// them, so their on_exit and on_enter systems (like despawning and spawning the enemys) do
+// not run and everything carries on where it was once Pause is popped again. The systems
+// of the paused state do not run while Pause is on top, the few that run in every state
+// (like animations) check for Pause themselves.
+
+use bevy::prelude::*;
+
+use crate::{GameState, player::Player, stats::{Stats, StatusEffects}, items::{ItemDatabase, ItemDatabaseHandle, Inventory, use_item, inventory_options}, equipment::{Equipment, EquipmentSlot, equip_item, unequip_item}, sprites::SpriteSheet, fadeout::ScreenFade, input::{ActionState, InputAction, InputMap, Rebinding}, menu::{Menu, MenuOption, spawn_menu, settings_options, pick_setting}, save::{SaveGame, SAVE_SLOTS, read_slots, slot_text}, text::{BitmapText, TextAlign, spawn_text}};
+
+pub struct PausePlugin;
+
+// everything on the pause screen is a child of this, which is a child of the camera
+#[derive(Component)]
+struct PauseScreen;
+
+// which list of options a menu on the pause screen is
+#[derive(Component, Clone, Copy, PartialEq, Eq)]
+enum PausePage {
+    Main,
+    Settings,
+    // the save slots to save to
+    Save,
+    Inventory,
+    // what the player is wearing, picking a slot takes it off
+    Equipment,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum PauseOption {
+    Resume,
+    Inventory,
+    Equipment,
+    Settings,
+    Save,
+    QuitToTitle,
+}
+
+impl PauseOption {
+    const ALL: [PauseOption; 6] = [
+        PauseOption::Resume,
+        PauseOption::Inventory,
+        PauseOption::Equipment,
+        PauseOption::Settings,
+        PauseOption::Save,
+        PauseOption::QuitToTitle,
+    ];
+
+    fn text(self) -> &'static str {
+        match self {
+            PauseOption::Resume => "Resume",
+            PauseOption::Inventory => "Inventory",
+            PauseOption::Equipment => "Equipment",
+            PauseOption::Settings => "Settings",
+            PauseOption::Save => "Save",
+            PauseOption::QuitToTitle => "Quit to Title",
+        }
+    }
+}
+
+const OPTION_SIZE: f32 = 0.08;
+
+impl Plugin for PausePlugin {
+    fn build(&self, app: &mut App) {
+        app
+            .add_system_set(SystemSet::on_update(GameState::Overworld).with_system(open_pause_menu))
+            .add_system_set(SystemSet::on_update(GameState::Combat).with_system(open_pause_menu))
+            .add_system_set(SystemSet::on_enter(GameState::Pause).with_system(spawn_pause_menu))
+            .add_system_set(SystemSet::on_update(GameState::Pause).with_system(pause_menu.after("menus")))
+            .add_system_set(SystemSet::on_exit(GameState::Pause).with_system(despawn_pause_menu));
+    }
+}
+
+fn open_pause_menu(
+    mut actions: ResMut<ActionState>,
+    mut state: ResMut<State<GameState>>,
+    // the state is about to change under a fadeout, so pausing waits until it is done
+    fade_query: Query<(), With<ScreenFade>>
+) {
+    if !actions.just_pressed(InputAction::Menu) || !fade_query.is_empty() { return; }
+
+    if state.push(GameState::Pause).is_ok() {
+        // so the same press does not close the menu straight away
+        actions.clear();
+    }
+}
+
+// the options of the first page, the ones that can not be picked are disabled
+fn main_options(state: &State<GameState>) -> Vec<MenuOption> {
+    PauseOption::ALL
+        .iter()
+        .map(|&option| {
+            let enabled = match option {
+                // a battle can not be saved, only the overworld
+                PauseOption::Save => state.inactives().last() == Some(&GameState::Overworld),
+                PauseOption::Resume | PauseOption::Inventory | PauseOption::Equipment | PauseOption::Settings | PauseOption::QuitToTitle => true,
+            };
+            MenuOption::new(option.text(), enabled)
+        })
+        .collect()
+}
+
+// every save slot can be saved over
+fn save_options() -> Vec<MenuOption> {
+    read_slots()
+        .iter()
+        .enumerate()
+        .map(|(slot, save)| MenuOption::new(&slot_text(slot, save), true))
+        .chain(std::iter::once(MenuOption::new("Back", true)))
+        .collect()
+}
+
+// the items the player is carrying, they are only used from here in the overworld, in a
+// battle they are used with the Item action so that using one takes a turn
+fn item_options(
+    state: &State<GameState>,
+    database: Option<&ItemDatabase>,
+    stats: &Stats,
+    inventory: &Inventory,
+    statuses: &StatusEffects
+) -> Vec<MenuOption> {
+    let mut options = inventory_options(database, inventory, stats, statuses, false);
+    if state.inactives().last() != Some(&GameState::Overworld) {
+        for option in options.iter_mut().take(inventory.items.len()) {
+            option.enabled = false;
+        }
+    }
+    options
+}
+
+// one option for every slot showing what is in it, only the slots with something
+// in them can be picked and only in the overworld, like the items
+fn equipment_options(state: &State<GameState>, database: Option<&ItemDatabase>, equipment: &Equipment) -> Vec<MenuOption> {
+    let in_overworld = state.inactives().last() == Some(&GameState::Overworld);
+    EquipmentSlot::ALL
+        .iter()
+        .map(|&slot| {
+            let item = equipment.get(slot);
+            let name = item.map_or("-", |id| {
+                database.and_then(|database| database.items.get(id)).map_or(id.as_str(), |definition| definition.name.as_str())
+            });
+            MenuOption::new(&format!("{:<10}{}", format!("{:?}", slot), name), item.is_some() && in_overworld)
+        })
+        .chain(std::iter::once(MenuOption::new("Back", true)))
+        .collect()
+}
+
+fn spawn_page(commands: &mut Commands, screen: Entity, page: PausePage, options: Vec<MenuOption>) {
+    // the top left of the options, the settings are wider so they start further left
+    let translation = match page {
+        PausePage::Main => Vec3::new(-0.4, 0.3, 0.1),
+        PausePage::Settings | PausePage::Save | PausePage::Inventory | PausePage::Equipment => Vec3::new(-1.0, 0.3, 0.1),
+    };
+    let menu = spawn_menu(commands, GameState::Pause, options, OPTION_SIZE, translation);
+    commands.entity(menu).insert(page);
+    commands.entity(screen).add_child(menu);
+}
+
+fn spawn_pause_menu(
+    mut commands: Commands,
+    // the pause screen is put in front of the camera
+    camera_query: Query<Entity, With<Camera>>,
+    state: Res<State<GameState>>,
+    sprite_sheet: Res<SpriteSheet>
+) {
+    // sprite index 0 is a solid tile, it is stretched over the whole screen to darken the game behind the menu
+    let mut sprite = TextureAtlasSprite::new(0);
+    sprite.color = Color::rgba(0.0, 0.0, 0.0, 0.6);
+    sprite.custom_size = Some(Vec2::splat(4.0));
+    let background = commands
+        .spawn_bundle(SpriteSheetBundle {
+            sprite,
+            texture_atlas: sprite_sheet.0.clone(),
+            ..Default::default()
+        })
+        .id();
+
+    let title = spawn_text(
+        &mut commands,
+        BitmapText {
+            text: String::from("Paused"),
+            size: 0.12,
+            color: Color::WHITE,
+            align: TextAlign::Center,
+            max_width: None,
+        },
+        Vec3::new(0.0, 0.6, 0.1)
+    );
+
+    let screen = commands
+        .spawn()
+        .insert(PauseScreen)
+        .insert(Name::new("Pause Menu"))
+        // in front of everything that is in front of the camera, like the dialogue box
+        .insert(Transform::from_xyz(0.0, 0.0, -0.5))
+        .insert(GlobalTransform::default())
+        .push_children(&[background, title])
+        .id();
+    spawn_page(&mut commands, screen, PausePage::Main, main_options(&state));
+
+    if let Some(camera) = camera_query.iter().next() {
+        commands.entity(camera).add_child(screen);
+    }
+}
+
+fn despawn_pause_menu(mut commands: Commands, screen_query: Query<Entity, With<PauseScreen>>) {
+    for entity in screen_query.iter() {
+        commands.entity(entity).despawn_recursive();
+    }
+}
+
+#[allow(clippy::too_many_arguments)]
+fn pause_menu(
+    // commands are used to switch pages
+    mut commands: Commands,
+    screen_query: Query<Entity, With<PauseScreen>>,
+    mut menu_query: Query<(Entity, &mut Menu, &PausePage)>,
+    mut state: ResMut<State<GameState>>,
+    // the menu action closes the pause menu like it opened it
+    mut actions: ResMut<ActionState>,
+    // the settings page shows and changes the key bindings
+    mut input_map: ResMut<InputMap>,
+    mut rebinding: ResMut<Rebinding>,
+    mut save_events: EventWriter<SaveGame>,
+    // items are used on the player from the inventory page, and put on and taken off
+    mut player_query: Query<(&mut Stats, &mut Inventory, &mut Equipment, &mut StatusEffects), With<Player>>,
+    item_database_handle: Res<ItemDatabaseHandle>,
+    item_databases: Res<Assets<ItemDatabase>>
+) {
+    let screen = match screen_query.get_single() {
+        Ok(screen) => screen,
+        Err(_) => return,
+    };
+    let (menu_entity, mut menu, &page) = match menu_query.get_single_mut() {
+        Ok(menu) => menu,
+        Err(_) => return,
+    };
+
+    match page {
+        PausePage::Main => {
+            let resume = actions.just_pressed(InputAction::Menu)
+                || menu.cancelled
+                || menu.picked.map(|picked| PauseOption::ALL[picked]) == Some(PauseOption::Resume);
+
+            if resume {
+                // the paused state carries on where it was
+                if state.pop().is_ok() {
+                    actions.clear();
+                }
+                return;
+            }
+
+            match menu.picked.map(|picked| PauseOption::ALL[picked]) {
+                Some(PauseOption::Settings) => {
+                    commands.entity(menu_entity).despawn_recursive();
+                    spawn_page(&mut commands, screen, PausePage::Settings, settings_options(&input_map, &rebinding));
+                }
+                Some(PauseOption::Inventory) => {
+                    commands.entity(menu_entity).despawn_recursive();
+                    let (stats, inventory, _, statuses) = player_query.single();
+                    let database = item_databases.get(&item_database_handle.0);
+                    spawn_page(&mut commands, screen, PausePage::Inventory, item_options(&state, database, stats, inventory, statuses));
+                }
+                Some(PauseOption::Equipment) => {
+                    commands.entity(menu_entity).despawn_recursive();
+                    let (_, _, equipment, _) = player_query.single();
+                    let database = item_databases.get(&item_database_handle.0);
+                    spawn_page(&mut commands, screen, PausePage::Equipment, equipment_options(&state, database, equipment));
+                }
+                Some(PauseOption::Save) => {
+                    commands.entity(menu_entity).despawn_recursive();
+                    spawn_page(&mut commands, screen, PausePage::Save, save_options());
+                }
+                Some(PauseOption::QuitToTitle) => {
+                    // replace takes every state off the stack, running their on_exit systems
+                    // so the map, the player and any battle are cleaned up, if a state change
+                    // is already queued the menu stays open and quit can be picked again
+                    if state.replace(GameState::MainMenu).is_ok() {
+                        actions.clear();
+                    }
+                }
+                Some(PauseOption::Resume) | None => (),
+            }
+        }
+        PausePage::Settings => {
+            // the keys change when an action is rebound
+            menu.options = settings_options(&input_map, &rebinding);
+
+            let back = match menu.picked {
+                Some(picked) => pick_setting(picked, &mut input_map, &mut rebinding),
+                None => menu.cancelled,
+            };
+            if back {
+                commands.entity(menu_entity).despawn_recursive();
+                spawn_page(&mut commands, screen, PausePage::Main, main_options(&state));
+            }
+        }
+        PausePage::Save => {
+            if let Some(slot) = menu.picked.filter(|&slot| slot < SAVE_SLOTS) {
+                save_events.send(SaveGame { slot });
+            }
+            // the menu goes back once the game is saved, or on Back
+            if menu.picked.is_some() || menu.cancelled {
+                commands.entity(menu_entity).despawn_recursive();
+                spawn_page(&mut commands, screen, PausePage::Main, main_options(&state));
+            }
+        }
+        PausePage::Inventory => {
+            let (mut stats, mut inventory, mut equipment, mut statuses) = player_query.single_mut();
+            let database = item_databases.get(&item_database_handle.0);
+
+            let back = match (menu.picked, database) {
+                // the last option is Back
+                (Some(index), _) if index >= inventory.items.len() => true,
+                (Some(index), Some(database)) => {
+                    // equipment is put on, everything else is used
+                    if !equip_item(database, index, &mut inventory, &mut equipment) {
+                        use_item(database, index, &mut inventory, &mut stats, &mut statuses, None);
+                    }
+                    false
+                }
+                _ => menu.cancelled,
+            };
+            if back {
+                commands.entity(menu_entity).despawn_recursive();
+                spawn_page(&mut commands, screen, PausePage::Main, main_options(&state));
+                return;
+            }
+
+            // the counts change when an item is used, and so do the items that would do something
+            menu.options = item_options(&state, database, &stats, &inventory, &statuses);
+        }
+        PausePage::Equipment => {
+            let (_, mut inventory, mut equipment, _) = player_query.single_mut();
+            let database = item_databases.get(&item_database_handle.0);
+
+            let back = match (menu.picked.and_then(|picked| EquipmentSlot::ALL.get(picked)), database) {
+                (Some(&slot), Some(database)) => {
+                    if !unequip_item(database, slot, &mut inventory, &mut equipment) {
+                        info!("There is no room to take the {:?} off", slot);
+                    }
+                    false
+                }
+                // Back
+                _ => menu.picked.is_some() || menu.cancelled,
+            };
+            if back {
+                commands.entity(menu_entity).despawn_recursive();
+                spawn_page(&mut commands, screen, PausePage::Main, main_options(&state));
+                return;
+            }
+
+            menu.options = equipment_options(&state, database, &equipment);
+        }
+    }
+}
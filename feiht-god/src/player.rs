@@ -1,27 +1,63 @@
 use bevy::{prelude::*, sprite::collide_aabb::collide};
 use bevy_inspector_egui::Inspectable;
 
-use crate::{TILE_SIZE, sprites::{spawn_sprite, Facing}, sprites::{SpriteSheet, AnimationTimer}, tilemap::{TileCollider, EncounterSpawner}, GameState, fadeout::create_fadeout};
+use crate::{TILE_SIZE, sprites::{spawn_sprite, Facing}, sprites::SpriteSheet, animation::Animator, tilemap::{TileCollider, EncounterSpawner, Warp, ChangeMap, PlayerSpawn, TileGrid, TileProperties, WorldHandle}, map::WorldAsset, GameState, GameRng, fadeout::{create_fadeout, create_warp_fadeout}, stats::{Stats, BaseStats, StatusEffects, Buffs}, items::{Inventory, ItemStack}, equipment::Equipment, leveling::Experience, collision::{move_and_slide, PLAYER_SIZE, WALL_SIZE}, input::ActionState, main_menu::NewGame, encounters::{EncounterDatabase, EncounterDatabaseHandle}};
 
 pub struct PlayerPlugin;
 
+// the walk and idle animations of the player, relative to the assets folder
+const PLAYER_ANIMATIONS: &str = "animations/player.anim.ron";
+
+// tiles the player can walk through encounter zones before the very first encounter
+const FIRST_ENCOUNTER_STEPS: f32 = 8.0;
+
 #[derive(Component, Default, Reflect)]
 #[reflect(Component)]
 pub struct EncounterTracker {
-    timer: Timer
+    // tiles left to walk through encounter zones before the next encounter
+    steps_left: f32,
+    // where the player was last frame, used to work out how far they walked
+    last_position: Vec3,
+}
+
+impl EncounterTracker {
+    // forget where the player was, used when the player is moved without walking there
+    pub fn reset_position(&mut self, position: Vec3) {
+        self.last_position = position;
+    }
 }
 
 #[derive(Component, Inspectable)]
 pub struct Player {
     pub is_moving: bool,
     pub is_active: bool,
-    speed: f32,
+    // fastest the player can walk, in tiles per second
+    pub max_speed: f32,
+    // how quickly the player gets up to speed, in tiles per second per second. the speed changes
+    // linearly (by the same amount every second) for both of these, there are no curves
+    pub acceleration: f32,
+    // how quickly the player stops once nothing is pressed, in tiles per second per second
+    pub friction: f32,
 }
 
+// how fast and in which direction something is moving, in tiles per second
+#[derive(Component, Inspectable, Default)]
+pub struct Velocity(pub Vec2);
+
+// tiles with this property change how slippery the ground is, the friction
+// and acceleration of the player are multiplied by it (ice would be below 1)
+const FRICTION_PROPERTY: &str = "friction";
+
+// the longest a frame is moved for in seconds, the walls are only looked for in the cells around
+// where the player is and where they are going, so a long frame (like dragging the window)
+// could otherwise move the player past a whole cell and through a wall
+const MAX_MOVE_TIME: f32 = 1.0 / 30.0;
+
 impl Plugin for PlayerPlugin {
     fn build(&self, app: &mut App) {
         app
             .add_startup_system(spawn_player)
+            .add_system(reset_player)
             .add_system_set(
                 SystemSet::on_enter(GameState::Overworld).with_system(show_player))
             .add_system_set(
@@ -31,7 +67,9 @@ impl Plugin for PlayerPlugin {
                 // execute camera system after player movement system
                 .with_system(camera_follow.after("movement"))
                 .with_system(animate_player_sprite)
-                .with_system(player_encounter_checking.after("movement"))
+                .with_system(player_encounter_checking.label("encounters").after("movement"))
+                // an encounter stops the player so they are not warped on the way to combat
+                .with_system(player_warp_checking.after("encounters"))
             );
     }
 }
@@ -89,39 +127,108 @@ fn show_player(
     }
 }
 
+#[allow(clippy::too_many_arguments)]
 fn player_encounter_checking(
     // commands will need to be used to spawn the fadeout here, they
-    // will be used to pass to the create_fadeout function
+    // will be used to pass to the create_fadeout function, and to insert the encounter
     mut commands: Commands,
     // query for the Player (to see if the player has moved), EncounterTracker 
-    // (to have each enemy come each time the timer is up), and the Transform 
+    // (to count the steps until the next encounter), and the Transform 
     // component (to check if the player is coliding with grass)
     mut player_query: Query<(&mut Player, &mut EncounterTracker, &Transform)>,
     // we will also need the Transform of the EncounterSpawner to compare with 
-    // the player transforn to see if the player is coliding with an EncounterSpawner
-    encounter_query: Query<&Transform, (With<EncounterSpawner>, Without<Player>)>,
-    // the time resource will be used to track time between each encounter
-    time: Res<Time>,
+    // the player transforn to see if the player is coliding with an EncounterSpawner,
+    // the EncounterSpawner itself holds the zone that is used to look up the encounter table
+    encounter_query: Query<(&Transform, &EncounterSpawner), Without<Player>>,
+    // only the EncounterSpawners in the cells around the player are checked
+    grid: Res<TileGrid>,
+    // the encounter tables of every zone
+    database_handle: Res<EncounterDatabaseHandle>,
+    databases: Res<Assets<EncounterDatabase>>,
+    // the rng picks the enemys and the number of steps until the next encounter
+    mut rng: ResMut<GameRng>,
     // the sprite sheet will be used to create the fadeout sprite
     sprite_sheet: Res<SpriteSheet>
 ) {
     let (mut player, mut encounter_tracker, player_translation) = player_query.single_mut();
     let player_translation = player_translation.translation;
 
-    // iterate through all the EncounterSpawner transforms returned from the query and if there is collision and the player has moved ...
-    if encounter_query
-        .iter()
-        .any(|&transform| wall_collision_check(player_translation, transform.translation)) 
-        && player.is_moving
+    // how many tiles the player walked since last frame
+    let walked = (player_translation - encounter_tracker.last_position).truncate().length() / TILE_SIZE;
+    encounter_tracker.last_position = player_translation;
+
+    if !player.is_moving { return; }
+
+    // find the EncounterSpawner the player is standing on, if there is none there is nothing to do
+    let zone = match grid
+        .around(player_translation)
+        .filter_map(|entity| encounter_query.get(entity).ok())
+        .find(|(transform, _)| wall_collision_check(player_translation, transform.translation))
+    {
+        Some((_, spawner)) => &spawner.zone,
+        None => return,
+    };
+
+    // zones without an encounter table (or a table that has not loaded yet) never have encounters
+    let table = match databases
+        .get(&database_handle.0)
+        .and_then(|database| database.zones.get(zone))
     {
-        // tick the timer every time the player is walking through an EncounterSpawner
-        encounter_tracker.timer.tick(time.delta());
+        Some(table) => table,
+        None => return,
+    };
+
+    // count down the steps every time the player is walking through an EncounterSpawner
+    encounter_tracker.steps_left -= walked;
+    if encounter_tracker.steps_left > 0.0 { return; }
+
+    // once the steps run out pick the enemys and switch to combat state
+    encounter_tracker.steps_left = table.roll_steps(&mut rng.0);
+    player.is_active = false;
+    commands.insert_resource(table.roll_encounter(&mut rng.0));
+    create_fadeout(&mut commands, GameState::Combat, &sprite_sheet);
+}
 
-        // every time the timer finishes switch to combat state
-        if encounter_tracker.timer.just_finished() {
-            player.is_active = false;
-            create_fadeout(&mut commands, GameState::Combat, &sprite_sheet);
+fn player_warp_checking(
+    // commands will be used to pass to the create_warp_fadeout function
+    mut commands: Commands,
+    // query for the Player (to stop the player from moving during the warp) and the
+    // Transform component (to check if the player is coliding with a warp)
+    mut player_query: Query<(&mut Player, &Transform)>,
+    // the Transform of every Warp to compare with the player transform, the Warp
+    // itself holds the map and spawn point the player is taken to
+    warp_query: Query<(&Transform, &Warp), Without<Player>>,
+    // only the Warps in the cells around the player are checked
+    grid: Res<TileGrid>,
+    // the world file has to have the map the warp goes to
+    world_handle: Res<WorldHandle>,
+    worlds: Res<Assets<WorldAsset>>,
+    // the sprite sheet will be used to create the fadeout sprite
+    sprite_sheet: Res<SpriteSheet>
+) {
+    let (mut player, player_transform) = player_query.single_mut();
+
+    // only walking onto a warp uses it, so the player is not warped again while fading
+    if !player.is_active || !player.is_moving { return; }
+
+    if let Some((_, warp)) = grid
+        .around(player_transform.translation)
+        .filter_map(|entity| warp_query.get(entity).ok())
+        .find(|(transform, _)| wall_collision_check(player_transform.translation, transform.translation))
+    {
+        // otherwise the player would be stopped for a map that is never spawned
+        if !worlds.get(&world_handle.0).is_some_and(|world| world.maps.contains_key(&warp.map)) {
+            warn!("There is no map \"{}\" in the world file", warp.map);
+            return;
         }
+
+        // the player can move again once the new map has been spawned
+        player.is_active = false;
+        player.is_moving = false;
+        create_warp_fadeout(&mut commands, ChangeMap {
+            map: warp.map.clone(),
+            spawn: PlayerSpawn::Point(warp.spawn_point.clone()),
+        }, &sprite_sheet);
     }
 }
 
@@ -144,77 +251,90 @@ fn camera_follow(
 }
 
 fn player_movement(
-    // query for the player, the players transform will need to be adjusted so it is a mutable reference 
-    mut player_query: Query<(&mut Player, &mut Transform, &mut Facing)>, 
+    // query for the player, the players transform and velocity will need to be adjusted so they are mutable references
+    mut player_query: Query<(&mut Player, &mut Transform, &mut Velocity, &mut Facing)>, 
     // query for walls with colliders, we will need the transform of the walls 
     // (again without player is required because the Player component could have a TileCollider component, meaning more than one result/entity)
     wall_query: Query<&Transform, (With<TileCollider>, Without<Player>)>,
-    // we will also need a keyboard input here so we will get the Input resource of type KeyCode
-    keyboard: Res<Input<KeyCode>>, 
+    // the grid is used to only check the walls in the cells around the player
+    grid: Res<TileGrid>,
+    // the properties of the tiles the player is standing on can change the friction
+    surface_query: Query<&TileProperties>,
+    // we will also need the input here so we will get the ActionState resource
+    actions: Res<ActionState>, 
     // we will use the Time resource to multiply by delta time
     time: Res<Time>,
 ) {
     // get the transform and player component out of the query
-    let (mut player, mut transform, mut facing) = player_query.single_mut();
+    let (mut player, mut transform, mut velocity, mut facing) = player_query.single_mut();
 
-    if !player.is_active { return; }
+    if !player.is_active {
+        // the player stops dead when they are stopped (for a warp or a battle)
+        velocity.0 = Vec2::ZERO;
+        player.is_moving = false;
+        return;
+    }
 
-    // (We can now check for keyboard input and edit the transform since we have a mutable reference to it)
+    // (We can now check for input and edit the transform since we have a mutable reference to it)
 
-    // add/subtract any movement from keypresses on the x axis
-    let mut x_delta = 0.0;
-    if keyboard.pressed(KeyCode::D) || keyboard.pressed(KeyCode::Right) {
-        *facing = Facing::Right;
-        x_delta += player.speed * TILE_SIZE * time.delta_seconds();
-    }
-    if keyboard.pressed(KeyCode::A) || keyboard.pressed(KeyCode::Left){
-        *facing = Facing::Left;
-        x_delta -= player.speed * TILE_SIZE * time.delta_seconds();
-    }
+    // which way the player is walking, the length is how far the stick is pushed (1 on a keyboard)
+    let movement = actions.movement();
 
-    // add/subtract any movement from keypresses on the y axis
-    let mut y_delta = 0.0;
-    if keyboard.pressed(KeyCode::W)  || keyboard.pressed(KeyCode::Up){
-        *facing = Facing::Up;
-        y_delta += player.speed * TILE_SIZE * time.delta_seconds();
-    }
-    if keyboard.pressed(KeyCode::S) || keyboard.pressed(KeyCode::Down){
-        *facing = Facing::Down;
-        y_delta -= player.speed * TILE_SIZE * time.delta_seconds();
-    }
+    // face the way the player is walking
+    facing.turn_towards(movement);
 
-    // diagnal
-    if y_delta != 0.0 && x_delta != 0.0 {
-        y_delta /= 1.5;
-        x_delta /= 1.5;
-    }
+    // how slippery the tile under the player is, 1 if the tile does not say
+    let surface = grid
+        .at(transform.translation)
+        .filter_map(|entity| surface_query.get(entity).ok())
+        .flat_map(|properties| properties.properties.iter())
+        .find(|property| property.name == FRICTION_PROPERTY)
+        .and_then(|property| property.value.parse::<f32>().ok())
+        .unwrap_or(1.0);
 
-    // the target as in where the player should be from the pressed buttons
-    let target = transform.translation + Vec3::new(x_delta, 0.0, 0.0);
-    // iterate through all the walls returned from the wall query and if there is no collision ...
-    if !wall_query
-        .iter()
-        .any(|&transform| wall_collision_check(target, transform.translation)) 
-    {
-        // update the players position to what they pressed
-        transform.translation = target;
-    }
+    // speed up towards the direction that is pressed (a stick that is only pushed part
+    // of the way walks slower), or slow down to a stop when nothing is pressed
+    let delta_seconds = time.delta_seconds().min(MAX_MOVE_TIME);
+    velocity.0 = if movement != Vec2::ZERO {
+        move_towards(velocity.0, movement * player.max_speed, player.acceleration * surface * delta_seconds)
+    } else {
+        move_towards(velocity.0, Vec2::ZERO, player.friction * surface * delta_seconds)
+    };
 
-    // the target as in where the player should be from the pressed buttons
-    let target = transform.translation + Vec3::new(0.0, y_delta, 0.0);
-    // iterate through all the walls returned from the wall query and if there is no collision ...
-    if !wall_query
-        .iter()
-        .any(|&transform| wall_collision_check(target, transform.translation)) 
-    {
-        // update the players position to what they pressed
-        transform.translation = target;
+    let delta = velocity.0 * TILE_SIZE * delta_seconds;
+    // the walls in the cells around where the player is and where they are going
+    let walls: Vec<Vec2> = grid
+        .around(transform.translation)
+        .chain(grid.around(transform.translation + delta.extend(0.0)))
+        .filter_map(|entity| wall_query.get(entity).ok())
+        .map(|wall| wall.translation.truncate())
+        .collect();
+    // move as far as the walls let the player, sliding along them and around corners
+    let slide = move_and_slide(transform.translation.truncate(), delta, &walls);
+    let moved = slide.position - transform.translation.truncate();
+    transform.translation.x = slide.position.x;
+    transform.translation.y = slide.position.y;
+
+    // running into a wall stops the player on that axis, unless they are being pushed around a
+    // corner, the push uses the blocked movement so it needs the speed to go at walking pace
+    if !slide.nudged {
+        for axis in 0..2 {
+            if moved[axis].abs() < delta[axis].abs() {
+                velocity.0[axis] = 0.0;
+            }
+        }
     }
 
-    if y_delta != 0.0 || x_delta != 0.0 {
-        player.is_moving = true;
+    player.is_moving = velocity.0 != Vec2::ZERO;
+}
+
+// move current towards target by at most max_step without going past it
+fn move_towards(current: Vec2, target: Vec2, max_step: f32) -> Vec2 {
+    let difference = target - current;
+    if difference.length() <= max_step {
+        target
     } else {
-        player.is_moving = false;
+        current + difference.normalize() * max_step
     }
 }
 
@@ -229,85 +349,123 @@ fn wall_collision_check(
         // center position of player collision rectangle
         target_player_pos,
         // dimensions of player collision rectangle
-        Vec2::splat(TILE_SIZE * 0.9),
+        Vec2::splat(PLAYER_SIZE),
         // center postion of wall collision rectangle
         wall_translation,
         // dimensions of wall collision rectangle
-        Vec2::splat(TILE_SIZE)
+        Vec2::splat(WALL_SIZE)
     );
     // if there is collision of any value return true (as in collision occured)
     collision.is_some()
 }
 
-// TODO: Add the other diagnal animations, create idle animations, and diagnal animation
 fn animate_player_sprite(
-    mut query: Query<(&mut TextureAtlasSprite, &mut Facing, &mut AnimationTimer, &Player)>,
-    time: Res<Time>
+    // the Animator plays the clip, we just pick which one from the way the player is facing
+    mut query: Query<(&mut Animator, &Facing, &Player)>
 ) {
-    let (mut sprite, direction, mut animation_timer,player) = query.get_single_mut().unwrap();
-
-    if !player.is_active { return; }
-
-    animation_timer.0.tick(time.delta());
-
-    // if the player has moved change the sprite to the movement in the particular direction
-    if player.is_moving {   
-        // every half a half a second (or every half of the animation timer duration),
-        // switch the sprite that is being displayed
-        if animation_timer.0.elapsed_secs() > animation_timer.0.duration().as_secs_f32() / 2.0{
-            sprite.index = match *direction {
-                Facing::Down => 7,
-                Facing::Up => 5,
-                Facing::Left => 3,
-                Facing::Right => 1,
-            }
-        } else {
-            sprite.index = match *direction {
-                Facing::Down => 8,
-                Facing::Up => 6,
-                Facing::Left => 4,
-                Facing::Right => 2,
-            }
-        }
-    } else { // if the player stops moving go to "idle" position
-        sprite.index = match *direction {
-            Facing::Down => 7,
-            Facing::Up => 5,
-            Facing::Left => 3,
-            Facing::Right => 1,
-        }
+    let (mut animator, direction, player) = query.single_mut();
+
+    // if the player has moved play the walk animation in the particular direction,
+    // if the player stops moving play the idle animation in the particular direction
+    let action = if player.is_moving { "walk" } else { "idle" };
+    animator.play(&format!("{}_{}", action, direction.name()));
+}
+
+// the stats the player has at the start of a game
+pub fn starting_stats() -> Stats {
+    Stats {
+        health: 40,
+        max_health: 40,
+        attack: 8,
+        defense: 2,
+        speed: 4,
+        level: 1,
+    }
+}
+
+// the items the player has at the start of a game
+fn starting_inventory() -> Inventory {
+    Inventory {
+        items: vec![ItemStack { item: String::from("potion"), count: 3 }],
     }
 }
 
+// the player starts with a sword in hand so they can fight in the overworld
+fn starting_equipment() -> Equipment {
+    Equipment {
+        weapon: Some(String::from("wooden_sword")),
+        ..Default::default()
+    }
+}
+
+// put the player back the way they are at the start of the game, the
+// spawn point of the start map moves them back to where they start
+#[allow(clippy::type_complexity)]
+fn reset_player(
+    mut new_game_events: EventReader<NewGame>,
+    mut player_query: Query<(&mut Stats, &mut BaseStats, &mut Experience, &mut Inventory, &mut Equipment, &mut StatusEffects, &mut EncounterTracker, &mut Velocity, &mut Facing), With<Player>>
+) {
+    if new_game_events.iter().count() == 0 { return; }
+
+    let (mut stats, mut base_stats, mut experience, mut inventory, mut equipment, mut statuses, mut encounter_tracker, mut velocity, mut facing) = player_query.single_mut();
+    *stats = starting_stats();
+    *base_stats = BaseStats::from_stats(&stats);
+    *experience = Experience::default();
+    *equipment = starting_equipment();
+    *inventory = starting_inventory();
+    *statuses = StatusEffects::default();
+    encounter_tracker.steps_left = FIRST_ENCOUNTER_STEPS;
+    velocity.0 = Vec2::ZERO;
+    *facing = Facing::Right;
+}
+
 fn spawn_player(
     // we need commands because we will be spawning a entity (inside of spawn_sprite),
     // we will also need commands because we will be adding components to our player entity
     mut commands: Commands, 
     // we will need the sprite sheet resource because we will be loading sprites
-    sprites: Res<SpriteSheet>
+    sprites: Res<SpriteSheet>,
+    // the animations of the player are loaded from a file
+    assets: Res<AssetServer>
 ) {
+    let translation = Vec3::new(5.0 * TILE_SIZE, -5.0 * TILE_SIZE, 900.0);
+
     // create a new player entity
     let player = spawn_sprite(
         &mut commands, 
         &sprites,
         // index 1 contains forward facing player sprite
         1,
-        Vec3::new(5.0 * TILE_SIZE, -5.0 * TILE_SIZE, 900.0),
+        translation,
         1.0
     );
 
     commands.entity(player)
         // add components to player entity
         .insert(Name::new("Player"))
+        // the player is shown once a game is started from the title screen
+        .insert(Visibility { is_visible: false })
         .insert(Player { 
-            speed: 4.0, 
+            max_speed: 4.0,
+            acceleration: 40.0,
+            friction: 50.0,
             is_active: true,
             is_moving: false 
         })
+        .insert(Velocity::default())
         .insert(Facing::Right)
-        .insert(AnimationTimer(Timer::from_seconds(0.25, true)))
+        .insert(Animator::new(assets.load(PLAYER_ANIMATIONS), "idle_right"))
         .insert(EncounterTracker {
-            timer: Timer::from_seconds(1.0, true)
-        });
+            steps_left: FIRST_ENCOUNTER_STEPS,
+            last_position: translation,
+        })
+        .insert(starting_stats())
+        .insert(BaseStats::from_stats(&starting_stats()))
+        .insert(Experience::default())
+        .insert(starting_inventory())
+        .insert(starting_equipment())
+        .insert(StatusEffects::default())
+        // only changed during a battle
+        .insert(Buffs::default());
 
 }
\ No newline at end of file
@@ -0,0 +1,310 @@
+// Saving and loading games. A game is saved to one of a few slots in the data folder
+// (~/.local/share/feiht-god/saves on linux) as a ron file that starts with the version of the
+// save format, saves written by an older version of the game are upgraded when they are read
+// (see migrate) so they can still be loaded after the format changes.
+
+use std::{fs, io::Write, path::{Path, PathBuf}};
+
+use bevy::prelude::*;
+use serde::{Deserialize, Serialize};
+
+use crate::{GameState, player::{Player, Velocity}, sprites::Facing, stats::{Stats, BaseStats, StatusEffects}, items::Inventory, equipment::Equipment, leveling::Experience, dialogue::WorldFlags, tilemap::CurrentMap, map::MapAsset, main_menu::NewGame};
+
+pub struct SavePlugin;
+
+// bumped whenever SaveData changes, with the old SaveData kept and a way to upgrade it added to migrate
+pub const SAVE_VERSION: u32 = 1;
+pub const SAVE_SLOTS: usize = 3;
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub struct SaveData {
+    pub version: u32,
+    // the name of the map in the world file and where the player was on it
+    pub map: String,
+    pub position: (f32, f32),
+    pub facing: Facing,
+    // the name of the map shown in the slot menus
+    pub location: String,
+    // the health and level are read from here, the rest of the stats are worked
+    // out again from the base stats and the equipment
+    pub stats: Stats,
+    pub base_stats: BaseStats,
+    // all the xp the player has gained
+    pub xp: u32,
+    // every world flag that was set, sorted so saving the same game twice writes the same file
+    pub flags: Vec<String>,
+    // in seconds
+    pub playtime: f64,
+    pub inventory: Inventory,
+    pub equipment: Equipment,
+    pub statuses: StatusEffects,
+}
+
+// the start of every save, read first to know which version the rest of the file is
+#[derive(Deserialize)]
+struct SaveHeader {
+    version: u32,
+}
+
+// how long the game has been played for in seconds, not counting the title screen or the pause menu
+#[derive(Default)]
+pub struct Playtime(pub f64);
+
+// sent to save the game to a slot
+pub struct SaveGame {
+    pub slot: usize,
+}
+
+// sent to put the player and the world back the way they were when the game was saved,
+// the map is changed by whoever sends this
+pub struct LoadGame(pub SaveData);
+
+impl SaveData {
+    fn path(slot: usize) -> Option<PathBuf> {
+        dirs::data_dir().map(|data| data.join("feiht-god").join("saves").join(format!("slot{}.ron", slot + 1)))
+    }
+
+    // the save in a slot, None if nothing has been saved there
+    pub fn read(slot: usize) -> Result<Option<SaveData>, String> {
+        match SaveData::path(slot) {
+            Some(path) => SaveData::read_from(&path),
+            None => Ok(None),
+        }
+    }
+
+    pub fn write(&self, slot: usize) -> Result<(), String> {
+        let path = SaveData::path(slot).ok_or("there is no data folder")?;
+        self.write_to(&path)
+    }
+
+    fn read_from(path: &Path) -> Result<Option<SaveData>, String> {
+        if !path.exists() { return Ok(None); }
+        let bytes = fs::read(path).map_err(|error| error.to_string())?;
+        let header: SaveHeader = ron::de::from_bytes(&bytes).map_err(|error| error.to_string())?;
+        migrate(header.version, &bytes).map(Some)
+    }
+
+    // the save is written next to the path and then moved over it, so a crash while
+    // saving leaves the old save there instead of half of the new one
+    fn write_to(&self, path: &Path) -> Result<(), String> {
+        if let Some(folder) = path.parent() {
+            fs::create_dir_all(folder).map_err(|error| error.to_string())?;
+        }
+        let text = ron::ser::to_string_pretty(self, ron::ser::PrettyConfig::default())
+            .map_err(|error| error.to_string())?;
+
+        let temporary = path.with_extension("ron.tmp");
+        let mut file = fs::File::create(&temporary).map_err(|error| error.to_string())?;
+        file.write_all(text.as_bytes()).map_err(|error| error.to_string())?;
+        file.sync_all().map_err(|error| error.to_string())?;
+        fs::rename(&temporary, path).map_err(|error| error.to_string())
+    }
+}
+
+// read a save written with any version of the format, when the format changes the save of the
+// version before is read here and upgraded to the current one
+fn migrate(version: u32, bytes: &[u8]) -> Result<SaveData, String> {
+    match version {
+        SAVE_VERSION => ron::de::from_bytes(bytes).map_err(|error| error.to_string()),
+        newer if newer > SAVE_VERSION => Err(format!("it was saved by a newer version of the game (save version {})", newer)),
+        older => Err(format!("there is no way to upgrade save version {}", older)),
+    }
+}
+
+// what is in every slot, for the slot menus
+pub fn read_slots() -> Vec<Result<Option<SaveData>, String>> {
+    (0..SAVE_SLOTS).map(SaveData::read).collect()
+}
+
+// one line for a slot menu, like "Slot 1  Overworld  Lv 3  1:02:03"
+pub fn slot_text(slot: usize, save: &Result<Option<SaveData>, String>) -> String {
+    match save {
+        Ok(Some(save)) => {
+            let seconds = save.playtime as u64;
+            format!(
+                "Slot {}  {}  Lv {}  {}:{:02}:{:02}",
+                slot + 1, save.location, save.stats.level, seconds / 3600, seconds / 60 % 60, seconds % 60
+            )
+        }
+        Ok(None) => format!("Slot {}  Empty", slot + 1),
+        Err(_) => format!("Slot {}  Can not be read", slot + 1),
+    }
+}
+
+impl Plugin for SavePlugin {
+    fn build(&self, app: &mut App) {
+        app
+            .init_resource::<Playtime>()
+            .add_event::<SaveGame>()
+            .add_event::<LoadGame>()
+            // the playtime only counts up while playing
+            .add_system_set(SystemSet::on_update(GameState::Overworld).with_system(count_playtime))
+            .add_system_set(SystemSet::on_update(GameState::Combat).with_system(count_playtime))
+            // games are saved from the pause menu and loaded from the title screen
+            .add_system(save_game)
+            .add_system(load_game)
+            .add_system(reset_playtime);
+    }
+}
+
+fn count_playtime(mut playtime: ResMut<Playtime>, time: Res<Time>) {
+    playtime.0 += time.delta_seconds_f64();
+}
+
+fn reset_playtime(mut new_game_events: EventReader<NewGame>, mut playtime: ResMut<Playtime>) {
+    if new_game_events.iter().count() > 0 {
+        playtime.0 = 0.0;
+    }
+}
+
+#[allow(clippy::type_complexity)]
+fn save_game(
+    mut save_events: EventReader<SaveGame>,
+    player_query: Query<(&Transform, &Facing, &Stats, &BaseStats, &Experience, &Inventory, &Equipment, &StatusEffects), With<Player>>,
+    // the map the player is on, by its name in the world file and the name that is shown
+    current_map: Res<CurrentMap>,
+    maps: Res<Assets<MapAsset>>,
+    flags: Res<WorldFlags>,
+    playtime: Res<Playtime>
+) {
+    for event in save_events.iter() {
+        let map = match &current_map.name {
+            Some(map) => map.clone(),
+            None => {
+                warn!("Can not save before a map has been loaded");
+                continue;
+            }
+        };
+        let location = current_map.handle
+            .as_ref()
+            .and_then(|handle| maps.get(handle))
+            .map_or_else(|| map.clone(), |asset| asset.metadata.name.clone());
+
+        let (transform, &facing, stats, base_stats, experience, inventory, equipment, statuses) = player_query.single();
+        let mut flags: Vec<String> = flags.0.iter().cloned().collect();
+        flags.sort();
+
+        let save = SaveData {
+            version: SAVE_VERSION,
+            map,
+            position: (transform.translation.x, transform.translation.y),
+            facing,
+            location,
+            stats: stats.clone(),
+            base_stats: base_stats.clone(),
+            xp: experience.0,
+            flags,
+            playtime: playtime.0,
+            inventory: inventory.clone(),
+            equipment: equipment.clone(),
+            statuses: statuses.clone(),
+        };
+        match save.write(event.slot) {
+            Ok(()) => info!("Saved the game to slot {}", event.slot + 1),
+            Err(error) => warn!("Can not save the game to slot {}: {}", event.slot + 1, error),
+        }
+    }
+}
+
+#[allow(clippy::type_complexity)]
+fn load_game(
+    mut load_events: EventReader<LoadGame>,
+    mut player_query: Query<(&mut Facing, &mut Stats, &mut BaseStats, &mut Experience, &mut Inventory, &mut Equipment, &mut StatusEffects, &mut Velocity), With<Player>>,
+    mut flags: ResMut<WorldFlags>,
+    mut playtime: ResMut<Playtime>
+) {
+    for LoadGame(save) in load_events.iter() {
+        let (mut facing, mut stats, mut base_stats, mut experience, mut inventory, mut equipment, mut statuses, mut velocity) = player_query.single_mut();
+        *facing = save.facing;
+        *stats = save.stats.clone();
+        *base_stats = save.base_stats.clone();
+        *experience = Experience(save.xp);
+        *inventory = save.inventory.clone();
+        *equipment = save.equipment.clone();
+        *statuses = save.statuses.clone();
+        velocity.0 = Vec2::ZERO;
+
+        *flags = WorldFlags(save.flags.iter().cloned().collect());
+        playtime.0 = save.playtime;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::player::starting_stats;
+
+    fn save() -> SaveData {
+        SaveData {
+            version: SAVE_VERSION,
+            map: "cave".to_string(),
+            position: (12.5, -3.0),
+            facing: Facing::Down,
+            location: "The Cave".to_string(),
+            stats: starting_stats(),
+            base_stats: BaseStats::from_stats(&starting_stats()),
+            xp: 42,
+            flags: vec!["met_the_god".to_string()],
+            playtime: 3723.0,
+            inventory: Inventory::default(),
+            equipment: Equipment::default(),
+            statuses: StatusEffects::default(),
+        }
+    }
+
+    // a folder of its own in the temp folder, emptied first in case an earlier run left it behind
+    fn temp_folder(name: &str) -> PathBuf {
+        let folder = std::env::temp_dir().join(format!("feiht-god-{}-{}", name, std::process::id()));
+        let _ = fs::remove_dir_all(&folder);
+        folder
+    }
+
+    #[test]
+    fn current_version_is_read_back() {
+        let save = save();
+        let text = ron::ser::to_string(&save).unwrap();
+        assert_eq!(migrate(SAVE_VERSION, text.as_bytes()), Ok(save));
+    }
+
+    #[test]
+    fn newer_versions_are_rejected() {
+        let mut save = save();
+        save.version = SAVE_VERSION + 1;
+        let text = ron::ser::to_string(&save).unwrap();
+        let error = migrate(SAVE_VERSION + 1, text.as_bytes()).unwrap_err();
+        assert!(error.contains("newer version"), "{}", error);
+    }
+
+    #[test]
+    fn garbage_is_an_error() {
+        let folder = temp_folder("garbage");
+        let path = folder.join("slot1.ron");
+        fs::create_dir_all(&folder).unwrap();
+        fs::write(&path, [0xff, 0x00, 0x13, 0x37]).unwrap();
+        assert!(SaveData::read_from(&path).is_err());
+
+        // a header with the rest of the save missing
+        fs::write(&path, format!("(version: {})", SAVE_VERSION)).unwrap();
+        assert!(SaveData::read_from(&path).is_err());
+        fs::remove_dir_all(&folder).unwrap();
+    }
+
+    #[test]
+    fn saves_are_written_and_read_from_a_folder() {
+        let folder = temp_folder("slots");
+        let path = folder.join("saves").join("slot1.ron");
+        assert_eq!(SaveData::read_from(&path), Ok(None));
+
+        let mut save = save();
+        save.write_to(&path).unwrap();
+        assert_eq!(SaveData::read_from(&path), Ok(Some(save.clone())));
+        // the temporary file was moved over the save
+        assert!(!path.with_extension("ron.tmp").exists());
+
+        // saving again replaces the old save
+        save.xp = 100;
+        save.write_to(&path).unwrap();
+        assert_eq!(SaveData::read_from(&path), Ok(Some(save)));
+        fs::remove_dir_all(&folder).unwrap();
+    }
+}
@@ -1,21 +1,82 @@
 use bevy::prelude::*;
 use bevy_inspector_egui::Inspectable;
+use serde::{Deserialize, Serialize};
 
 use crate::{TILE_SIZE};
 
 pub struct SpritePlugin;
 
-#[derive(Component, Inspectable)]
+// the directions are in order counter clockwise starting from the right,
+// every one covers 45 degrees around its direction
+#[derive(Component, Inspectable, Serialize, Deserialize, Clone, Copy, PartialEq, Eq, Debug)]
 pub enum Facing {
+    Right,
+    UpRight,
     Up,
-    Down,
+    UpLeft,
     Left,
-    Right
+    DownLeft,
+    Down,
+    DownRight,
 }
 
-#[derive(Component, Default, Reflect)]
-#[reflect(Component)]
-pub struct AnimationTimer(pub Timer);
+// how far past the edge of its 45 degrees a direction has to go before facing changes,
+// so a stick held right on the edge does not flicker between two directions
+const FACING_HYSTERESIS: f32 = 10.0;
+
+impl Facing {
+    const ALL: [Facing; 8] = [
+        Facing::Right,
+        Facing::UpRight,
+        Facing::Up,
+        Facing::UpLeft,
+        Facing::Left,
+        Facing::DownLeft,
+        Facing::Down,
+        Facing::DownRight,
+    ];
+
+    // angle of the direction in degrees, counter clockwise from the right
+    fn angle(self) -> f32 {
+        Facing::ALL.iter().position(|&facing| facing == self).unwrap() as f32 * 45.0
+    }
+
+    // a vector of length 1 pointing the way this direction faces
+    pub fn direction(self) -> Vec2 {
+        let angle = self.angle().to_radians();
+        Vec2::new(angle.cos(), angle.sin())
+    }
+
+    // the name used in animation clips, like "walk_up_left"
+    pub fn name(self) -> &'static str {
+        match self {
+            Facing::Right => "right",
+            Facing::UpRight => "up_right",
+            Facing::Up => "up",
+            Facing::UpLeft => "up_left",
+            Facing::Left => "left",
+            Facing::DownLeft => "down_left",
+            Facing::Down => "down",
+            Facing::DownRight => "down_right",
+        }
+    }
+
+    // face the closest of the eight directions to the direction (holding two keys faces diagonally),
+    // a zero direction keeps facing the same way
+    pub fn turn_towards(&mut self, direction: Vec2) {
+        if direction == Vec2::ZERO { return; }
+
+        let angle = direction.y.atan2(direction.x).to_degrees();
+        // difference between the two angles in degrees, from -180 to 180
+        let difference = |facing: Facing| (angle - facing.angle() + 540.0).rem_euclid(360.0) - 180.0;
+
+        // stay facing the same way until the direction is clearly closer to another one
+        if difference(*self).abs() <= 22.5 + FACING_HYSTERESIS { return; }
+
+        let index = (angle / 45.0).round().rem_euclid(8.0) as usize;
+        *self = Facing::ALL[index];
+    }
+}
 
 // create my own resource that holds a copy of the specific sprites sheet handle
 pub struct SpriteSheet(pub Handle<TextureAtlas>);
@@ -0,0 +1,189 @@
+use bevy::prelude::*;
+use bevy_inspector_egui::Inspectable;
+use serde::{Deserialize, Serialize};
+
+// the numbers every fighter (the player and each enemy) brings into combat
+#[derive(Component, Inspectable, Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub struct Stats {
+    pub health: i32,
+    pub max_health: i32,
+    pub attack: i32,
+    pub defense: i32,
+    // fighters with a higher speed act earlier in the turn order
+    pub speed: i32,
+    pub level: u32,
+}
+
+impl Stats {
+    pub fn is_alive(&self) -> bool {
+        self.health > 0
+    }
+
+    // take damage without going below 0 health
+    pub fn damage(&mut self, amount: i32) {
+        self.health = (self.health - amount).max(0);
+    }
+
+    // heal without going above max health
+    pub fn heal(&mut self, amount: i32) {
+        self.health = (self.health + amount).min(self.max_health);
+    }
+}
+
+// lasting effects on a fighter that carry over between battles until they are cured
+#[derive(Serialize, Deserialize, Clone, Copy, PartialEq, Eq, Hash, Debug)]
+pub enum Status {
+    // loses some health at the end of every turn in battle
+    Poison,
+}
+
+// a poisoned fighter loses this fraction of their max health every turn (at least 1)
+pub const POISON_FRACTION: f32 = 1.0 / 8.0;
+
+#[derive(Component, Serialize, Deserialize, Clone, Default, Debug, PartialEq)]
+pub struct StatusEffects(pub Vec<Status>);
+
+impl StatusEffects {
+    pub fn has(&self, status: Status) -> bool {
+        self.0.contains(&status)
+    }
+
+    pub fn add(&mut self, status: Status) {
+        if !self.has(status) {
+            self.0.push(status);
+        }
+    }
+
+    // the health lost to the statuses at the end of a turn in battle
+    pub fn turn_damage(&self, stats: &Stats) -> i32 {
+        if self.has(Status::Poison) {
+            ((stats.max_health as f32 * POISON_FRACTION) as i32).max(1)
+        } else {
+            0
+        }
+    }
+
+    // true if the status was there to be cured
+    pub fn cure(&mut self, status: Status) -> bool {
+        let before = self.0.len();
+        self.0.retain(|&other| other != status);
+        self.0.len() != before
+    }
+}
+
+// bonuses from items that only last until the end of the battle
+#[derive(Component, Clone, Default, Debug, PartialEq)]
+pub struct Buffs {
+    pub attack: i32,
+    pub defense: i32,
+}
+
+// the stats of the player before their equipment and buffs are added, the Stats
+// of the player are worked out from these with derive_stats whenever either changes
+#[derive(Component, Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub struct BaseStats {
+    pub max_health: i32,
+    pub attack: i32,
+    pub defense: i32,
+    pub speed: i32,
+}
+
+impl BaseStats {
+    // the base stats of a fighter without any equipment or buffs
+    pub fn from_stats(stats: &Stats) -> Self {
+        BaseStats {
+            max_health: stats.max_health,
+            attack: stats.attack,
+            defense: stats.defense,
+            speed: stats.speed,
+        }
+    }
+}
+
+// what a piece of equipment adds to the stats of the player
+#[derive(Deserialize, Clone, Default, Debug, PartialEq)]
+#[serde(default)]
+pub struct StatBonus {
+    pub max_health: i32,
+    pub attack: i32,
+    pub defense: i32,
+    pub speed: i32,
+}
+
+// the base stats with every bonus and buff added, the health and level are kept from the
+// current stats (the health is capped to the new max health). no stat goes below 0 and
+// the max health never goes below 1
+pub fn derive_stats(base: &BaseStats, bonuses: &[&StatBonus], buffs: &Buffs, current: &Stats) -> Stats {
+    let max_health = base.max_health + bonuses.iter().map(|bonus| bonus.max_health).sum::<i32>();
+    let attack = base.attack + bonuses.iter().map(|bonus| bonus.attack).sum::<i32>() + buffs.attack;
+    let defense = base.defense + bonuses.iter().map(|bonus| bonus.defense).sum::<i32>() + buffs.defense;
+    let speed = base.speed + bonuses.iter().map(|bonus| bonus.speed).sum::<i32>();
+    let max_health = max_health.max(1);
+
+    Stats {
+        health: current.health.min(max_health),
+        max_health,
+        attack: attack.max(0),
+        defense: defense.max(0),
+        speed: speed.max(0),
+        level: current.level,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn base() -> BaseStats {
+        BaseStats { max_health: 40, attack: 8, defense: 2, speed: 4 }
+    }
+
+    fn current(health: i32) -> Stats {
+        Stats { health, max_health: 40, attack: 8, defense: 2, speed: 4, level: 7 }
+    }
+
+    #[test]
+    fn bonuses_and_buffs_are_added_to_the_base_stats() {
+        let sword = StatBonus { attack: 2, ..Default::default() };
+        let armor = StatBonus { max_health: 5, defense: 2, ..Default::default() };
+        let charm = StatBonus { attack: 1, speed: 2, ..Default::default() };
+        let buffs = Buffs { attack: 3, defense: 1 };
+
+        let stats = derive_stats(&base(), &[&sword, &armor, &charm], &buffs, &current(30));
+        assert_eq!(stats, Stats { health: 30, max_health: 45, attack: 14, defense: 5, speed: 6, level: 7 });
+    }
+
+    #[test]
+    fn nothing_equipped_gives_the_base_stats() {
+        let stats = derive_stats(&base(), &[], &Buffs::default(), &current(40));
+        assert_eq!(stats, current(40));
+    }
+
+    #[test]
+    fn stats_do_not_go_below_0() {
+        let cursed = StatBonus { max_health: -100, attack: -20, defense: -20, speed: -20 };
+        let stats = derive_stats(&base(), &[&cursed], &Buffs { attack: -5, defense: -5 }, &current(30));
+        assert_eq!((stats.attack, stats.defense, stats.speed), (0, 0, 0));
+        // max health never goes below 1
+        assert_eq!(stats.max_health, 1);
+        assert_eq!(stats.health, 1);
+    }
+
+    #[test]
+    fn health_is_capped_to_the_new_max_health() {
+        let armor = StatBonus { max_health: 5, ..Default::default() };
+        let stats = derive_stats(&base(), &[&armor], &Buffs::default(), &current(45));
+        assert_eq!(stats.health, 45);
+
+        // taking the armor off takes away the extra health
+        let stats = derive_stats(&base(), &[], &Buffs::default(), &stats);
+        assert_eq!((stats.health, stats.max_health), (40, 40));
+    }
+
+    #[test]
+    fn level_is_kept() {
+        let stats = derive_stats(&base(), &[], &Buffs { attack: 1, defense: 1 }, &current(10));
+        assert_eq!(stats.level, 7);
+        assert_eq!(stats.health, 10);
+    }
+}
@@ -0,0 +1,368 @@
+// Fighting in the overworld without going into combat. The player swings their weapon
+// the way they are facing, hurting the enemys standing on the map and cutting down tiles with
+// the breakable property set to true. Walking into an enemy hurts the player.
+
+use bevy::{prelude::*, sprite::collide_aabb::collide};
+
+use crate::{TILE_SIZE, GameState, GameRng, animation::{Animator, AnimationEvent, SpriteAnimations}, player::Player, sprites::{Facing, SpriteSheet, spawn_sprite}, fadeout::create_fadeout, stats::{Stats, BaseStats}, damage::calculate_damage, collision::{PLAYER_SIZE, WALL_SIZE}, input::{ActionState, InputAction}, tilemap::{OverworldEnemy, TileGrid, TileProperties}, enemies::{EnemyDatabase, EnemyDatabaseHandle}, items::{ItemDatabase, ItemDatabaseHandle, Inventory}, equipment::Equipment, leveling::{Experience, GrowthCurves, GrowthCurvesHandle, gain_xp}, text::{BitmapText, TextAlign, spawn_text}};
+
+pub struct SwordPlugin;
+
+// index of the sword pointing up in Sprites.png, it is turned to point the way it is swung
+const SWING_SPRITE: usize = 12;
+// the swing clip steps the sword across the arc one frame at a time, and its last frame
+// sends the event that ends the swing
+const SWING_ANIMATIONS: &str = "animations/sword.anim.ron";
+const SWING_CLIP: &str = "swing";
+const SWING_END_EVENT: &str = "swing_end";
+// the frames of the clip that sweep the sword, from one side of the arc to the other
+const SWING_STEPS: usize = 5;
+// the angle the sword sweeps through in radians, centered on the way the player is facing
+const SWING_ARC: f32 = std::f32::consts::FRAC_PI_2;
+// how far in front of the player the sword and its hitbox are, in tiles
+const SWING_REACH: f32 = 0.8;
+// tiles with this property set to true are cut down by the sword
+const BREAKABLE_PROPERTY: &str = "breakable";
+// after being hurt the player can not be hurt again for this long
+const INVULNERABLE_TIME: f32 = 1.0;
+// how much closer than touching the player has to be to an enemy to be hurt by it, the
+// enemys block the player like walls so the player can never get inside them
+const CONTACT_MARGIN: f32 = TILE_SIZE * 0.1;
+const FLASH_TIME: f32 = 0.2;
+const FLOATING_TEXT_TIME: f32 = 1.0;
+const FLOATING_TEXT_SIZE: f32 = 0.05;
+// in front of everything on the map but behind the fadeout
+const FLOATING_TEXT_Z: f32 = 980.0;
+
+// kept as a resource so the clips are loaded before the first swing and never unloaded
+struct SwingAnimations(Handle<SpriteAnimations>);
+
+// the sword while it is being swung, a child of the player
+#[derive(Component)]
+struct SwordSwing {
+    // the way the player was facing when the swing started
+    direction: Vec2,
+    // everything the swing has hit already, so nothing is hit twice by one swing
+    hit: Vec<Entity>,
+}
+
+#[derive(Component)]
+struct Invulnerable(Timer);
+
+// tints a sprite red for a moment after it is hurt
+#[derive(Component)]
+struct HitFlash(Timer);
+
+// text that rises up from where something happened and disappears, like the xp from an enemy
+#[derive(Component)]
+struct FloatingText(Timer);
+
+impl Plugin for SwordPlugin {
+    fn build(&self, app: &mut App) {
+        app
+            .add_startup_system(load_swing_animations)
+            .add_system_set(SystemSet::on_update(GameState::Overworld)
+                .with_system(give_enemy_stats)
+                .with_system(start_swing.label("start_swing").after("movement"))
+                .with_system(swing_sword.after("start_swing"))
+                .with_system(end_swings)
+                .with_system(enemy_contact.after("movement"))
+                .with_system(tick_invulnerable)
+                .with_system(flash_sprites)
+                .with_system(float_text)
+            )
+            .add_system_set(SystemSet::on_exit(GameState::Overworld).with_system(despawn_swings));
+    }
+}
+
+fn load_swing_animations(mut commands: Commands, assets: Res<AssetServer>) {
+    commands.insert_resource(SwingAnimations(assets.load(SWING_ANIMATIONS)));
+}
+
+// the enemys on a map get their stats once the enemy database has loaded
+fn give_enemy_stats(
+    mut commands: Commands,
+    enemy_query: Query<(Entity, &OverworldEnemy), Without<Stats>>,
+    database_handle: Res<EnemyDatabaseHandle>,
+    databases: Res<Assets<EnemyDatabase>>
+) {
+    let database = match databases.get(&database_handle.0) {
+        Some(database) => database,
+        None => return,
+    };
+
+    for (entity, enemy) in enemy_query.iter() {
+        match database.enemies.get(&enemy.id) {
+            Some(definition) => {
+                commands.entity(entity).insert(definition.stats.to_stats(1));
+            }
+            None => {
+                // without stats it can not be hurt, so it is taken off the map
+                warn!("Enemy \"{}\" is not defined", enemy.id);
+                commands.entity(entity).despawn_recursive();
+            }
+        }
+    }
+}
+
+fn start_swing(
+    mut commands: Commands,
+    actions: Res<ActionState>,
+    // only a player with a weapon in hand can swing it
+    player_query: Query<(Entity, &Player, &Facing, &Equipment)>,
+    swing_query: Query<(), With<SwordSwing>>,
+    sprite_sheet: Res<SpriteSheet>,
+    // a swing is only ended by its clip, so there is no swinging until the clip has loaded
+    swing_animations: Res<SwingAnimations>,
+    animations: Res<Assets<SpriteAnimations>>
+) {
+    if !actions.just_pressed(InputAction::Attack) || !swing_query.is_empty() { return; }
+    if !animations.get(&swing_animations.0).is_some_and(|animations| animations.clips.contains_key(SWING_CLIP)) { return; }
+
+    let (player, player_info, facing, equipment) = player_query.single();
+    // not while talking or warping
+    if !player_info.is_active || equipment.weapon.is_none() { return; }
+
+    let sword = spawn_sprite(&mut commands, &sprite_sheet, SWING_SPRITE, Vec3::new(0.0, 0.0, 0.5), 1.0);
+    commands.entity(sword)
+        .insert(SwordSwing {
+            direction: facing.direction(),
+            hit: Vec::new(),
+        })
+        .insert(Animator::new(swing_animations.0.clone(), SWING_CLIP))
+        .insert(Name::new("Sword Swing"));
+    commands.entity(player).add_child(sword);
+}
+
+#[allow(clippy::too_many_arguments, clippy::type_complexity)]
+fn swing_sword(
+    // commands are used to despawn what the sword cuts down and defeats
+    mut commands: Commands,
+    mut swing_query: Query<(&mut SwordSwing, &Animator, &mut Transform)>,
+    // the player is hurting the enemys with their stats, and gets the xp and loot of the enemys
+    mut player_query: Query<(&Transform, &mut Stats, &mut BaseStats, &mut Experience, &mut Inventory), (With<Player>, Without<SwordSwing>)>,
+    mut enemy_query: Query<(&OverworldEnemy, &mut Stats, &Transform), (Without<Player>, Without<SwordSwing>)>,
+    tile_query: Query<(&Transform, &TileProperties), (Without<Player>, Without<SwordSwing>)>,
+    // only what is in the cells around the hitbox can be hit, and what is despawned is taken off the grid
+    mut grid: ResMut<TileGrid>,
+    mut rng: ResMut<GameRng>,
+    enemy_database_handle: Res<EnemyDatabaseHandle>,
+    enemy_databases: Res<Assets<EnemyDatabase>>,
+    growth_handle: Res<GrowthCurvesHandle>,
+    growth_curves: Res<Assets<GrowthCurves>>,
+    item_database_handle: Res<ItemDatabaseHandle>,
+    item_databases: Res<Assets<ItemDatabase>>
+) {
+    let (mut swing, animator, mut transform) = match swing_query.get_single_mut() {
+        Ok(swing) => swing,
+        Err(_) => return,
+    };
+
+    // sweep from one side of the way the player is facing to the other a step every frame of the clip
+    let progress = animator.frame().min(SWING_STEPS - 1) as f32 / (SWING_STEPS - 1) as f32;
+    let angle = swing.direction.y.atan2(swing.direction.x) + SWING_ARC * (0.5 - progress);
+    let offset = Vec2::new(angle.cos(), angle.sin()) * SWING_REACH * TILE_SIZE;
+    transform.translation.x = offset.x;
+    transform.translation.y = offset.y;
+    // the sword sprite points up, so it is turned a quarter turn less than the angle
+    transform.rotation = Quat::from_rotation_z(angle - std::f32::consts::FRAC_PI_2);
+
+    let (player_transform, mut player_stats, mut base_stats, mut experience, mut inventory) = player_query.single_mut();
+    let hitbox = player_transform.translation + (swing.direction * SWING_REACH * TILE_SIZE).extend(0.0);
+    let hits: Vec<Entity> = grid
+        .around(hitbox)
+        .filter(|entity| !swing.hit.contains(entity))
+        .collect();
+
+    for entity in hits {
+        if let Ok((enemy, mut enemy_stats, enemy_transform)) = enemy_query.get_mut(entity) {
+            if collide(hitbox, Vec2::splat(TILE_SIZE), enemy_transform.translation, Vec2::splat(WALL_SIZE)).is_none() { continue; }
+            swing.hit.push(entity);
+
+            let hit = calculate_damage(&player_stats, &enemy_stats, 1.0, false, &mut rng.0);
+            enemy_stats.damage(hit.damage);
+            commands.entity(entity).insert(HitFlash(Timer::from_seconds(FLASH_TIME, false)));
+            info!("Player hit {} for {} damage (critical: {})", enemy.id, hit.damage, hit.critical);
+
+            if enemy_stats.is_alive() { continue; }
+
+            // a defeated enemy gives its rewards straight away, there is no summary like after a battle
+            commands.entity(entity).despawn_recursive();
+            grid.remove(TileGrid::cell(enemy_transform.translation), entity);
+            let definition = match enemy_databases.get(&enemy_database_handle.0).and_then(|database| database.enemies.get(&enemy.id)) {
+                Some(definition) => definition,
+                None => continue,
+            };
+            let mut lines = vec![(format!("+{} xp", definition.xp_reward), Color::WHITE)];
+
+            if let Some(curves) = growth_curves.get(&growth_handle.0) {
+                for level_up in gain_xp(curves, definition.xp_reward, &mut experience, &mut player_stats, &mut base_stats) {
+                    lines.push((format!("Level {}!", level_up.level), Color::YELLOW));
+                }
+            } else {
+                experience.0 += definition.xp_reward;
+            }
+
+            let item_database = item_databases.get(&item_database_handle.0);
+            for item in definition.roll_loot(&mut rng.0) {
+                if let Some(item_definition) = item_database.and_then(|database| database.items.get(&item)) {
+                    if inventory.add(&item, 1, item_definition.max_stack) == 0 {
+                        lines.push((item_definition.name.clone(), Color::WHITE));
+                    }
+                }
+            }
+
+            for (i, (line, color)) in lines.iter().enumerate() {
+                let translation = enemy_transform.translation.truncate() + Vec2::new(0.0, i as f32 * -FLOATING_TEXT_SIZE * 1.5);
+                spawn_floating_text(&mut commands, line, *color, translation);
+            }
+        } else if let Ok((tile_transform, properties)) = tile_query.get(entity) {
+            let breakable = properties.properties
+                .iter()
+                .any(|property| property.name == BREAKABLE_PROPERTY && property.value == "true");
+            if !breakable { continue; }
+            if collide(hitbox, Vec2::splat(TILE_SIZE), tile_transform.translation, Vec2::splat(WALL_SIZE)).is_none() { continue; }
+
+            swing.hit.push(entity);
+            commands.entity(entity).despawn_recursive();
+            grid.remove(TileGrid::cell(tile_transform.translation), entity);
+        }
+    }
+}
+
+// the swing is over once its clip reaches the frame with the end event
+fn end_swings(
+    mut commands: Commands,
+    mut events: EventReader<AnimationEvent>,
+    swing_query: Query<(), With<SwordSwing>>
+) {
+    for event in events.iter() {
+        if event.name == SWING_END_EVENT && swing_query.contains(event.entity) {
+            commands.entity(event.entity).despawn_recursive();
+        }
+    }
+}
+
+fn spawn_floating_text(commands: &mut Commands, text: &str, color: Color, translation: Vec2) {
+    let text = spawn_text(
+        commands,
+        BitmapText {
+            text: text.to_string(),
+            size: FLOATING_TEXT_SIZE,
+            color,
+            align: TextAlign::Center,
+            max_width: None,
+        },
+        translation.extend(FLOATING_TEXT_Z)
+    );
+    commands.entity(text).insert(FloatingText(Timer::from_seconds(FLOATING_TEXT_TIME, false)));
+}
+
+// walking into an enemy hurts the player like an attack from it would in combat
+#[allow(clippy::type_complexity)]
+fn enemy_contact(
+    mut commands: Commands,
+    mut player_query: Query<(Entity, &mut Player, &Transform, &mut Stats, Option<&Invulnerable>)>,
+    enemy_query: Query<(&Transform, &Stats), (With<OverworldEnemy>, Without<Player>)>,
+    grid: Res<TileGrid>,
+    mut rng: ResMut<GameRng>,
+    // the sprite sheet is used to create the fadeout to the game over screen
+    sprite_sheet: Res<SpriteSheet>
+) {
+    let (player_entity, mut player, player_transform, mut player_stats, invulnerable) = player_query.single_mut();
+    if !player.is_active || invulnerable.is_some() { return; }
+
+    let touching = grid
+        .around(player_transform.translation)
+        .filter_map(|entity| enemy_query.get(entity).ok())
+        .find(|(transform, _)| collide(
+            player_transform.translation,
+            Vec2::splat(PLAYER_SIZE + CONTACT_MARGIN * 2.0),
+            transform.translation,
+            Vec2::splat(WALL_SIZE)
+        ).is_some());
+    let enemy_stats = match touching {
+        Some((_, stats)) => stats,
+        None => return,
+    };
+
+    let hit = calculate_damage(enemy_stats, &player_stats, 1.0, false, &mut rng.0);
+    player_stats.damage(hit.damage);
+    commands.entity(player_entity)
+        .insert(Invulnerable(Timer::from_seconds(INVULNERABLE_TIME, false)))
+        .insert(HitFlash(Timer::from_seconds(FLASH_TIME, false)));
+    info!("An enemy hit the player for {} damage", hit.damage);
+
+    if !player_stats.is_alive() {
+        info!("Player was defeated");
+        player.is_active = false;
+        create_fadeout(&mut commands, GameState::GameOver, &sprite_sheet);
+    }
+}
+
+fn tick_invulnerable(
+    mut commands: Commands,
+    mut invulnerable_query: Query<(Entity, &mut Invulnerable)>,
+    time: Res<Time>
+) {
+    for (entity, mut invulnerable) in invulnerable_query.iter_mut() {
+        invulnerable.0.tick(time.delta());
+        if invulnerable.0.finished() {
+            commands.entity(entity).remove::<Invulnerable>();
+        }
+    }
+}
+
+fn flash_sprites(
+    mut commands: Commands,
+    mut flash_query: Query<(Entity, &mut HitFlash, &mut TextureAtlasSprite)>,
+    time: Res<Time>
+) {
+    for (entity, mut flash, mut sprite) in flash_query.iter_mut() {
+        flash.0.tick(time.delta());
+        if flash.0.finished() {
+            sprite.color = Color::WHITE;
+            commands.entity(entity).remove::<HitFlash>();
+        } else {
+            sprite.color = Color::RED;
+        }
+    }
+}
+
+fn float_text(
+    mut commands: Commands,
+    mut text_query: Query<(Entity, &mut FloatingText, &mut Transform, &mut BitmapText)>,
+    time: Res<Time>
+) {
+    for (entity, mut floating, mut transform, mut text) in text_query.iter_mut() {
+        floating.0.tick(time.delta());
+        if floating.0.finished() {
+            commands.entity(entity).despawn_recursive();
+            continue;
+        }
+
+        // rise half a tile a second and fade out over the second half
+        transform.translation.y += TILE_SIZE * 0.5 * time.delta_seconds();
+        let alpha = (2.0 * (1.0 - floating.0.percent())).min(1.0);
+        if (text.color.a() - alpha).abs() > 0.05 {
+            text.color.set_a(alpha);
+        }
+    }
+}
+
+// a swing or floating text left when the overworld is left would still be there after a battle,
+// and the flashes only run in the overworld so a sprite that was flashing would stay red
+#[allow(clippy::type_complexity)]
+fn despawn_swings(
+    mut commands: Commands,
+    despawn_query: Query<Entity, Or<(With<SwordSwing>, With<FloatingText>)>>,
+    mut flash_query: Query<(Entity, &mut TextureAtlasSprite), With<HitFlash>>
+) {
+    for entity in despawn_query.iter() {
+        commands.entity(entity).despawn_recursive();
+    }
+    for (entity, mut sprite) in flash_query.iter_mut() {
+        sprite.color = Color::WHITE;
+        commands.entity(entity).remove::<HitFlash>();
+    }
+}
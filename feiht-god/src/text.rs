@@ -0,0 +1,226 @@
+// Text drawn with the bitmap font in assets/Ascii.png so it matches the pixel art. Every
+// character is a sprite from the font sheet (laid out in ascii order), the characters are
+// spawned as children of the entity with the BitmapText component, so the text can be placed
+// in the world like any other sprite, or made a child of the camera to stay on screen.
+
+use bevy::prelude::*;
+
+use crate::GameState;
+
+pub struct TextPlugin;
+
+// the sprite sheet of the font, like SpriteSheet
+pub struct FontSheet(pub Handle<TextureAtlas>);
+
+// the characters of the font are 10 pixels but only the left 8 are used, so
+// characters are placed this fraction of the text size apart
+const CHARACTER_ADVANCE: f32 = 0.8;
+// lines are placed this fraction of the text size apart
+const LINE_HEIGHT: f32 = 1.1;
+// shown for characters that are not in the font
+const MISSING_CHARACTER: char = '?';
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum TextAlign {
+    // the translation of the text is the left edge of every line
+    Left,
+    // the translation of the text is the middle of every line
+    Center,
+    // the translation of the text is the right edge of every line
+    Right,
+}
+
+// a block of text, the translation of the entity is the top of the first line and
+// the glyphs are children of the entity (so it should not have any other children)
+#[derive(Component, Clone)]
+pub struct BitmapText {
+    pub text: String,
+    // height of a character in world units
+    pub size: f32,
+    pub color: Color,
+    pub align: TextAlign,
+    // lines longer than this (in world units) are wrapped at the last space
+    pub max_width: Option<f32>,
+}
+
+// shows the characters of a text one at a time, like someone is typing them
+#[derive(Component)]
+pub struct TextReveal {
+    pub characters_per_second: f32,
+    // how many characters are shown, this counts up
+    shown: f32,
+}
+
+impl TextReveal {
+    pub fn new(characters_per_second: f32) -> Self {
+        TextReveal { characters_per_second, shown: 0.0 }
+    }
+
+    // show every character straight away
+    pub fn finish(&mut self) {
+        self.shown = f32::INFINITY;
+    }
+
+    // start revealing again from the first character, for when the text is changed
+    pub fn restart(&mut self) {
+        self.shown = 0.0;
+    }
+
+    pub fn is_finished(&self, text: &BitmapText) -> bool {
+        self.shown >= text.text.chars().count() as f32
+    }
+}
+
+// one character of a text, the number is where the character is in the text
+#[derive(Component)]
+struct Glyph(usize);
+
+impl Plugin for TextPlugin {
+    fn build(&self, app: &mut App) {
+        app
+            .add_startup_system_to_stage(StartupStage::PreStartup, load_font)
+            // text is laid out in the update stage so the characters are in place
+            // (their transforms are worked out in the post update stage) before they are drawn
+            .add_system(layout_text.label("layout_text"))
+            .add_system(reveal_text.after("layout_text"));
+    }
+}
+
+fn load_font(
+    mut commands: Commands,
+    assets: Res<AssetServer>,
+    mut texture_atlases: ResMut<Assets<TextureAtlas>>
+) {
+    let image = assets.load("Ascii.png");
+    // 16 by 16 characters of 10 pixels with 1 pixel between them
+    let atlas = TextureAtlas::from_grid_with_padding(
+        image,
+        Vec2::splat(10.0),
+        16,
+        16,
+        Vec2::splat(1.0)
+    );
+    commands.insert_resource(FontSheet(texture_atlases.add(atlas)));
+}
+
+// spawn an entity to hold a text, the glyphs are added to it once the text is laid out
+pub fn spawn_text(commands: &mut Commands, text: BitmapText, translation: Vec3) -> Entity {
+    commands
+        .spawn()
+        .insert(Transform::from_translation(translation))
+        .insert(GlobalTransform::default())
+        .insert(Name::new(format!("Text \"{}\"", text.text)))
+        .insert(text)
+        .id()
+}
+
+// split the text into lines of at most max_characters, breaking at spaces where possible,
+// every character keeps its place in the text so the lines can be revealed in order
+pub fn wrap_text(text: &str, max_characters: Option<usize>) -> Vec<Vec<(usize, char)>> {
+    let mut lines = Vec::new();
+    let mut line: Vec<(usize, char)> = Vec::new();
+
+    for (index, character) in text.chars().enumerate() {
+        if character == '\n' {
+            lines.push(std::mem::take(&mut line));
+            continue;
+        }
+        line.push((index, character));
+
+        let max_characters = match max_characters {
+            Some(max_characters) if line.len() > max_characters => max_characters.max(1),
+            _ => continue,
+        };
+
+        // break after the last space, or in the middle of a word that is longer than the line
+        let split = match line.iter().rposition(|&(_, character)| character == ' ') {
+            Some(space) if space > 0 => space + 1,
+            _ => max_characters,
+        };
+        let rest = line.split_off(split);
+        // spaces at the end of a line are not drawn so they do not move the line
+        while line.last().is_some_and(|&(_, character)| character == ' ') {
+            line.pop();
+        }
+        lines.push(std::mem::replace(&mut line, rest));
+    }
+
+    lines.push(line);
+    lines
+}
+
+fn layout_text(
+    // commands are used to despawn the old characters and spawn the new ones
+    mut commands: Commands,
+    // only texts that were just spawned or changed are laid out again
+    text_query: Query<(Entity, &BitmapText, Option<&TextReveal>), Changed<BitmapText>>,
+    font: Res<FontSheet>
+) {
+    for (entity, text, reveal) in text_query.iter() {
+        commands.entity(entity).despawn_descendants();
+
+        let advance = text.size * CHARACTER_ADVANCE;
+        let max_characters = text.max_width.map(|max_width| (max_width / advance).floor() as usize);
+
+        let mut glyphs = Vec::new();
+        for (row, line) in wrap_text(&text.text, max_characters).iter().enumerate() {
+            let width = line.len() as f32 * advance;
+            let left = match text.align {
+                TextAlign::Left => 0.0,
+                TextAlign::Center => -width / 2.0,
+                TextAlign::Right => -width,
+            };
+
+            for (column, &(index, character)) in line.iter().enumerate() {
+                if character == ' ' { continue; }
+                let character = if character.is_ascii() { character } else { MISSING_CHARACTER };
+
+                let mut sprite = TextureAtlasSprite::new(character as usize);
+                sprite.color = text.color;
+                sprite.custom_size = Some(Vec2::splat(text.size));
+
+                let glyph = commands
+                    .spawn_bundle(SpriteSheetBundle {
+                        sprite,
+                        texture_atlas: font.0.clone(),
+                        transform: Transform::from_xyz(
+                            left + (column as f32 + 0.5) * advance,
+                            -(row as f32 * text.size * LINE_HEIGHT) - text.size / 2.0,
+                            0.0
+                        ),
+                        // revealed text starts with nothing shown
+                        visibility: Visibility {
+                            is_visible: reveal.is_none_or(|reveal| (index as f32) < reveal.shown),
+                        },
+                        ..Default::default()
+                    })
+                    .insert(Glyph(index))
+                    .id();
+                glyphs.push(glyph);
+            }
+        }
+
+        commands.entity(entity).push_children(&glyphs);
+    }
+}
+
+fn reveal_text(
+    mut reveal_query: Query<&mut TextReveal>,
+    // every character with the text it belongs to
+    mut glyph_query: Query<(&Glyph, &Parent, &mut Visibility)>,
+    // text stops being revealed while the game is paused
+    state: Res<State<GameState>>,
+    time: Res<Time>
+) {
+    if *state.current() == GameState::Pause { return; }
+
+    for mut reveal in reveal_query.iter_mut() {
+        reveal.shown += reveal.characters_per_second * time.delta_seconds();
+    }
+
+    for (glyph, parent, mut visibility) in glyph_query.iter_mut() {
+        if let Ok(reveal) = reveal_query.get(parent.0) {
+            visibility.is_visible = (glyph.0 as f32) < reveal.shown;
+        }
+    }
+}
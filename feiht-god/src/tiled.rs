@@ -0,0 +1,728 @@
+// Import for maps made with the Tiled editor (https://www.mapeditor.org), both the JSON (.tmj)
+// and the XML (.tmx) formats are turned into the same MapAsset as a .map.ron file.
+//
+// The tileset has to be Sprites.png (15x15 tiles, 1 pixel spacing) so the tile ids match the
+// sprite indexs, tile layers must be saved as CSV. Tiles use these custom properties:
+//   collider (bool)           the player can not walk through the tile
+//   encounter_zone (string)   walking over the tile can start an encounter from this zone
+// every other tile property is kept in the properties of the tile definition.
+// Tile layers can have a kind (string) property, one of ground (the default), decoration,
+// collision or overhead, see LayerKind.
+// Objects are read from object layers by their class (type in older versions of Tiled):
+//   spawn                     a spawn point named after the object
+//   warp                      a warp with the properties map (string) and spawn_point (string)
+//   npc                       a character, the sprite is the tile of a tile object or the sprite (int) property
+//   enemy                     an enemy fought with the sword, the sprite like an npc and the enemy (string) property
+
+use std::path::Path;
+
+use bevy::{prelude::*, asset::{AssetLoader, LoadContext, LoadedAsset, BoxedFuture}, utils::HashMap};
+use serde::Deserialize;
+
+use crate::map::{LayerKind, MapAsset, MapError, MapLayer, MapMetadata, MapObject, MapObjectKind, TileDefinition};
+
+// the top 3 bits of a gid are used by Tiled to flip the tile
+const GID_FLAGS: u32 = 0xE000_0000;
+
+type Properties = HashMap<String, String>;
+
+// a Tiled map after being read from either format
+struct TiledMap {
+    tile_width: f32,
+    tile_height: f32,
+    properties: Properties,
+    layers: Vec<TiledLayer>,
+    tilesets: Vec<TiledTileset>,
+}
+
+enum TiledLayer {
+    Tiles {
+        name: String,
+        kind: LayerKind,
+        width: usize,
+        height: usize,
+        gids: Vec<u32>,
+    },
+    Objects(Vec<TiledObject>),
+}
+
+struct TiledObject {
+    name: String,
+    class: String,
+    x: f32,
+    y: f32,
+    gid: Option<u32>,
+    properties: Properties,
+}
+
+struct TiledTileset {
+    first_gid: u32,
+    // path of an external tileset, relative to the map
+    source: Option<String>,
+    // the properties of every tile that has any, by the id of the tile in the tileset
+    tiles: HashMap<u32, Properties>,
+}
+
+impl TiledMap {
+    // find the tileset a gid belongs to and the id of the tile in that tileset
+    fn find_tile(&self, gid: u32) -> Option<(&TiledTileset, u32)> {
+        self.tilesets
+            .iter()
+            .filter(|tileset| tileset.first_gid <= gid)
+            .max_by_key(|tileset| tileset.first_gid)
+            .map(|tileset| (tileset, gid - tileset.first_gid))
+    }
+
+    fn into_map_asset(self, default_name: &str) -> Result<MapAsset, MapError> {
+        let mut tiles = Vec::new();
+        // every gid that is used gets one tile definition
+        let mut tile_indexs: HashMap<u32, usize> = HashMap::default();
+        let mut layers = Vec::new();
+        let mut spawn_points = HashMap::default();
+        let mut objects = Vec::new();
+
+        for layer in self.layers.iter() {
+            match layer {
+                TiledLayer::Tiles { name, kind, width, height, gids } => {
+                    if gids.len() != width * height {
+                        return Err(MapError::Invalid(format!(
+                            "layer \"{}\" has {} tiles but should have {}", name, gids.len(), width * height
+                        )));
+                    }
+
+                    let mut cells = Vec::with_capacity(gids.len());
+                    for &gid in gids.iter() {
+                        let gid = gid & !GID_FLAGS;
+                        if gid == 0 {
+                            cells.push(None);
+                            continue;
+                        }
+
+                        let index = match tile_indexs.get(&gid) {
+                            Some(&index) => index,
+                            None => {
+                                let (tileset, id) = self.find_tile(gid).ok_or_else(|| {
+                                    MapError::Invalid(format!("tile {} is not in any tileset", gid))
+                                })?;
+                                let mut properties = tileset.tiles.get(&id).cloned().unwrap_or_default();
+
+                                tiles.push(TileDefinition {
+                                    sprite: id as usize,
+                                    collider: properties.remove("collider").is_some_and(|value| value == "true"),
+                                    encounter_zone: properties.remove("encounter_zone"),
+                                    properties,
+                                });
+                                tile_indexs.insert(gid, tiles.len() - 1);
+                                tiles.len() - 1
+                            }
+                        };
+                        cells.push(Some(index));
+                    }
+
+                    layers.push(MapLayer { name: name.clone(), kind: *kind, width: *width, cells });
+                }
+                TiledLayer::Objects(tiled_objects) => {
+                    for object in tiled_objects.iter() {
+                        let gid = object.gid.map(|gid| gid & !GID_FLAGS);
+                        // tile objects are positioned by their bottom left corner instead of the top left
+                        let y = if gid.is_some() { object.y - self.tile_height } else { object.y };
+                        let position = (
+                            (object.x / self.tile_width).floor() as i32,
+                            (y / self.tile_height).floor() as i32,
+                        );
+                        let property = |name: &str| {
+                            object.properties.get(name).cloned().ok_or_else(|| MapError::Invalid(format!(
+                                "{} object \"{}\" is missing the {} property", object.class, object.name, name
+                            )))
+                        };
+
+                        let kind = match object.class.to_lowercase().as_str() {
+                            "spawn" => {
+                                spawn_points.insert(object.name.clone(), position);
+                                continue;
+                            }
+                            "warp" => MapObjectKind::Warp {
+                                map: property("map")?,
+                                spawn_point: property("spawn_point")?,
+                            },
+                            "npc" => {
+                                let sprite = match gid.and_then(|gid| self.find_tile(gid)) {
+                                    Some((_, id)) => id as usize,
+                                    None => property("sprite")?.parse().map_err(|_| MapError::Invalid(format!(
+                                        "npc object \"{}\" has a sprite property that is not a number", object.name
+                                    )))?,
+                                };
+                                MapObjectKind::Npc { sprite, dialogue: object.properties.get("dialogue").cloned() }
+                            }
+                            "enemy" => {
+                                let sprite = match gid.and_then(|gid| self.find_tile(gid)) {
+                                    Some((_, id)) => id as usize,
+                                    None => property("sprite")?.parse().map_err(|_| MapError::Invalid(format!(
+                                        "enemy object \"{}\" has a sprite property that is not a number", object.name
+                                    )))?,
+                                };
+                                MapObjectKind::Enemy { sprite, enemy: property("enemy")? }
+                            }
+                            other => {
+                                warn!("Skipping object \"{}\" with unknown class \"{}\"", object.name, other);
+                                continue;
+                            }
+                        };
+
+                        objects.push(MapObject { name: object.name.clone(), kind, position });
+                    }
+                }
+            }
+        }
+
+        Ok(MapAsset {
+            metadata: MapMetadata {
+                name: self.properties.get("name").cloned().unwrap_or_else(|| default_name.to_string()),
+            },
+            tiles,
+            layers,
+            spawn_points,
+            objects,
+        })
+    }
+}
+
+// the kind of a tile layer from its kind property
+fn layer_kind(name: &str, properties: &Properties) -> Result<LayerKind, MapError> {
+    match properties.get("kind").map(|kind| kind.to_lowercase()).as_deref() {
+        None | Some("ground") => Ok(LayerKind::Ground),
+        Some("decoration") => Ok(LayerKind::Decoration),
+        Some("collision") => Ok(LayerKind::Collision),
+        Some("overhead") => Ok(LayerKind::Overhead),
+        Some(other) => Err(MapError::Invalid(format!("layer \"{}\" has an unknown kind \"{}\"", name, other))),
+    }
+}
+
+// the JSON format (.tmj and .tsj)
+
+#[derive(Deserialize)]
+struct JsonProperty {
+    name: String,
+    value: serde_json::Value,
+}
+
+fn json_properties(properties: Vec<JsonProperty>) -> Properties {
+    properties
+        .into_iter()
+        .map(|property| {
+            // strings are stored without their quotes, everything else as it is written
+            let value = match property.value {
+                serde_json::Value::String(value) => value,
+                value => value.to_string(),
+            };
+            (property.name, value)
+        })
+        .collect()
+}
+
+#[derive(Deserialize)]
+struct JsonTile {
+    id: u32,
+    #[serde(default)]
+    properties: Vec<JsonProperty>,
+}
+
+#[derive(Deserialize)]
+struct JsonTileset {
+    // external tilesets have no firstgid in their own file
+    #[serde(default)]
+    firstgid: u32,
+    source: Option<String>,
+    #[serde(default)]
+    tiles: Vec<JsonTile>,
+}
+
+impl From<JsonTileset> for TiledTileset {
+    fn from(tileset: JsonTileset) -> Self {
+        TiledTileset {
+            first_gid: tileset.firstgid,
+            source: tileset.source,
+            tiles: tileset.tiles
+                .into_iter()
+                .map(|tile| (tile.id, json_properties(tile.properties)))
+                .collect(),
+        }
+    }
+}
+
+#[derive(Deserialize)]
+struct JsonObject {
+    #[serde(default)]
+    name: String,
+    // Tiled 1.9 renamed type to class
+    #[serde(default, rename = "type")]
+    kind: String,
+    #[serde(default)]
+    class: String,
+    x: f32,
+    y: f32,
+    gid: Option<u32>,
+    #[serde(default)]
+    properties: Vec<JsonProperty>,
+}
+
+#[derive(Deserialize)]
+#[serde(tag = "type", rename_all = "lowercase")]
+enum JsonLayer {
+    Tilelayer {
+        name: String,
+        width: usize,
+        height: usize,
+        // an array of gids when saved as CSV, a string when saved as base64
+        data: serde_json::Value,
+        #[serde(default)]
+        properties: Vec<JsonProperty>,
+    },
+    Objectgroup {
+        objects: Vec<JsonObject>,
+    },
+    // image and group layers are not supported
+    #[serde(other)]
+    Other,
+}
+
+#[derive(Deserialize)]
+struct JsonMap {
+    tilewidth: f32,
+    tileheight: f32,
+    #[serde(default)]
+    infinite: bool,
+    layers: Vec<JsonLayer>,
+    tilesets: Vec<JsonTileset>,
+    #[serde(default)]
+    properties: Vec<JsonProperty>,
+}
+
+fn json_error(error: serde_json::Error) -> MapError {
+    MapError::Parse {
+        line: error.line(),
+        column: error.column(),
+        message: error.to_string(),
+    }
+}
+
+fn parse_json_map(bytes: &[u8]) -> Result<TiledMap, MapError> {
+    let map: JsonMap = serde_json::from_slice(bytes).map_err(json_error)?;
+    if map.infinite {
+        return Err(MapError::Invalid(String::from("infinite maps are not supported")));
+    }
+
+    let mut layers = Vec::new();
+    for layer in map.layers {
+        match layer {
+            JsonLayer::Tilelayer { name, width, height, data, properties } => {
+                let gids = serde_json::from_value(data).map_err(|_| MapError::Invalid(format!(
+                    "layer \"{}\" has to be saved in the CSV tile layer format", name
+                )))?;
+                let kind = layer_kind(&name, &json_properties(properties))?;
+                layers.push(TiledLayer::Tiles { name, kind, width, height, gids });
+            }
+            JsonLayer::Objectgroup { objects } => {
+                layers.push(TiledLayer::Objects(objects
+                    .into_iter()
+                    .map(|object| TiledObject {
+                        name: object.name,
+                        class: if object.class.is_empty() { object.kind } else { object.class },
+                        x: object.x,
+                        y: object.y,
+                        gid: object.gid,
+                        properties: json_properties(object.properties),
+                    })
+                    .collect()
+                ));
+            }
+            JsonLayer::Other => {}
+        }
+    }
+
+    Ok(TiledMap {
+        tile_width: map.tilewidth,
+        tile_height: map.tileheight,
+        properties: json_properties(map.properties),
+        layers,
+        tilesets: map.tilesets.into_iter().map(TiledTileset::from).collect(),
+    })
+}
+
+fn parse_json_tileset(bytes: &[u8]) -> Result<HashMap<u32, Properties>, MapError> {
+    let tileset: JsonTileset = serde_json::from_slice(bytes).map_err(json_error)?;
+    Ok(TiledTileset::from(tileset).tiles)
+}
+
+// the XML format (.tmx and .tsx)
+
+fn xml_error(error: roxmltree::Error) -> MapError {
+    let position = error.pos();
+    MapError::Parse {
+        line: position.row as usize,
+        column: position.col as usize,
+        message: error.to_string(),
+    }
+}
+
+// read a number attribute, missing or broken numbers are an error
+fn xml_number<T: std::str::FromStr>(node: roxmltree::Node, name: &str) -> Result<T, MapError> {
+    node.attribute(name)
+        .and_then(|value| value.parse().ok())
+        .ok_or_else(|| MapError::Invalid(format!(
+            "<{}> on line {} needs a {} number", node.tag_name().name(), node.document().text_pos_at(node.range().start).row, name
+        )))
+}
+
+fn xml_properties(node: roxmltree::Node) -> Properties {
+    node.children()
+        .filter(|child| child.has_tag_name("properties"))
+        .flat_map(|properties| properties.children().filter(|child| child.has_tag_name("property")))
+        .filter_map(|property| {
+            // multi line strings are stored as the text of the property instead of the value
+            let value = property.attribute("value").or_else(|| property.text())?;
+            Some((property.attribute("name")?.to_string(), value.to_string()))
+        })
+        .collect()
+}
+
+fn xml_tileset_tiles(tileset: roxmltree::Node) -> Result<HashMap<u32, Properties>, MapError> {
+    tileset.children()
+        .filter(|child| child.has_tag_name("tile"))
+        .map(|tile| Ok((xml_number(tile, "id")?, xml_properties(tile))))
+        .collect()
+}
+
+fn parse_xml_map(text: &str) -> Result<TiledMap, MapError> {
+    let document = roxmltree::Document::parse(text).map_err(xml_error)?;
+    let map = document.root_element();
+    if map.attribute("infinite") == Some("1") {
+        return Err(MapError::Invalid(String::from("infinite maps are not supported")));
+    }
+
+    let mut layers = Vec::new();
+    let mut tilesets = Vec::new();
+    for node in map.children() {
+        match node.tag_name().name() {
+            "tileset" => tilesets.push(TiledTileset {
+                first_gid: xml_number(node, "firstgid")?,
+                source: node.attribute("source").map(String::from),
+                tiles: xml_tileset_tiles(node)?,
+            }),
+            "layer" => {
+                let name = node.attribute("name").unwrap_or_default().to_string();
+                let data = node.children().find(|child| child.has_tag_name("data"));
+                let gids = match data {
+                    Some(data) if data.attribute("encoding") == Some("csv") => data
+                        .text()
+                        .unwrap_or_default()
+                        .split(',')
+                        .map(|gid| gid.trim().parse())
+                        .collect::<Result<Vec<u32>, _>>()
+                        .map_err(|_| MapError::Invalid(format!("layer \"{}\" has a tile that is not a number", name)))?,
+                    _ => return Err(MapError::Invalid(format!(
+                        "layer \"{}\" has to be saved in the CSV tile layer format", name
+                    ))),
+                };
+
+                layers.push(TiledLayer::Tiles {
+                    kind: layer_kind(&name, &xml_properties(node))?,
+                    width: xml_number(node, "width")?,
+                    height: xml_number(node, "height")?,
+                    name,
+                    gids,
+                });
+            }
+            "objectgroup" => {
+                let mut objects = Vec::new();
+                for object in node.children().filter(|child| child.has_tag_name("object")) {
+                    objects.push(TiledObject {
+                        name: object.attribute("name").unwrap_or_default().to_string(),
+                        class: object.attribute("class").or_else(|| object.attribute("type")).unwrap_or_default().to_string(),
+                        x: xml_number(object, "x")?,
+                        y: xml_number(object, "y")?,
+                        gid: object.attribute("gid").and_then(|gid| gid.parse().ok()),
+                        properties: xml_properties(object),
+                    });
+                }
+                layers.push(TiledLayer::Objects(objects));
+            }
+            _ => {}
+        }
+    }
+
+    Ok(TiledMap {
+        tile_width: xml_number(map, "tilewidth")?,
+        tile_height: xml_number(map, "tileheight")?,
+        properties: xml_properties(map),
+        layers,
+        tilesets,
+    })
+}
+
+fn parse_xml_tileset(text: &str) -> Result<HashMap<u32, Properties>, MapError> {
+    let document = roxmltree::Document::parse(text).map_err(xml_error)?;
+    xml_tileset_tiles(document.root_element())
+}
+
+fn is_xml(path: &Path) -> bool {
+    matches!(path.extension().and_then(|extension| extension.to_str()), Some("tmx") | Some("tsx"))
+}
+
+fn parse_tileset(path: &Path, bytes: &[u8]) -> Result<HashMap<u32, Properties>, MapError> {
+    if is_xml(path) {
+        let text = std::str::from_utf8(bytes).map_err(|error| MapError::Invalid(error.to_string()))?;
+        parse_xml_tileset(text)
+    } else {
+        parse_json_tileset(bytes)
+    }
+}
+
+#[derive(Default)]
+pub struct TiledLoader;
+
+impl AssetLoader for TiledLoader {
+    fn load<'a>(
+        &'a self,
+        bytes: &'a [u8],
+        load_context: &'a mut LoadContext,
+    ) -> BoxedFuture<'a, Result<(), anyhow::Error>> {
+        Box::pin(async move {
+            let path = load_context.path().to_path_buf();
+            // put the path in front of the error so it is clear which file is broken
+            let in_file = |path: &Path, error: MapError| anyhow::anyhow!("{}, {}", path.display(), error);
+
+            let mut map = if is_xml(&path) {
+                let text = std::str::from_utf8(bytes)?;
+                parse_xml_map(text)
+            } else {
+                parse_json_map(bytes)
+            }.map_err(|error| in_file(&path, error))?;
+
+            // external tilesets are stored next to the map, read them so their tile properties are known
+            for tileset in map.tilesets.iter_mut() {
+                if let Some(source) = &tileset.source {
+                    let tileset_path = path.parent().unwrap_or_else(|| Path::new("")).join(source);
+                    let bytes = load_context.read_asset_bytes(&tileset_path).await?;
+                    tileset.tiles = parse_tileset(&tileset_path, &bytes).map_err(|error| in_file(&tileset_path, error))?;
+                }
+            }
+
+            let default_name = path.file_stem().and_then(|stem| stem.to_str()).unwrap_or_default();
+            let map = map.into_map_asset(default_name).map_err(|error| in_file(&path, error))?;
+            load_context.set_default_asset(LoadedAsset::new(map));
+            Ok(())
+        })
+    }
+
+    fn extensions(&self) -> &[&str] {
+        &["tmj", "tmx"]
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // 2147483746 is the gid 98 flipped horizontally, 1073741858 is 34 flipped vertically
+    // and 536871040 is 128 flipped diagonally
+    const JSON_MAP: &str = r#"{
+        "tilewidth": 15, "tileheight": 15, "infinite": false,
+        "properties": [{"name": "name", "type": "string", "value": "Cave"}],
+        "tilesets": [{"firstgid": 1, "tiles": [
+            {"id": 97, "properties": [{"name": "collider", "type": "bool", "value": true}]},
+            {"id": 33, "properties": [
+                {"name": "encounter_zone", "type": "string", "value": "cave"},
+                {"name": "depth", "type": "int", "value": 2}
+            ]}
+        ]}],
+        "layers": [
+            {"type": "tilelayer", "name": "ground", "width": 3, "height": 2, "data": [98, 2147483746, 0, 34, 1073741858, 98]},
+            {"type": "tilelayer", "name": "roof", "width": 1, "height": 1, "data": [98],
+                "properties": [{"name": "kind", "type": "string", "value": "overhead"}]},
+            {"type": "objectgroup", "objects": [
+                {"name": "start", "type": "spawn", "x": 16, "y": 31},
+                {"name": "door", "class": "warp", "x": 30, "y": 0, "properties": [
+                    {"name": "map", "type": "string", "value": "overworld"},
+                    {"name": "spawn_point", "type": "string", "value": "cave"}
+                ]},
+                {"name": "old man", "class": "npc", "gid": 536871040, "x": 15, "y": 30},
+                {"name": "face", "class": "enemy", "x": 0, "y": 15, "properties": [
+                    {"name": "sprite", "type": "int", "value": 127},
+                    {"name": "enemy", "type": "string", "value": "face"}
+                ]}
+            ]},
+            {"type": "imagelayer", "name": "sky"}
+        ]
+    }"#;
+
+    const XML_MAP: &str = r#"<?xml version="1.0" encoding="UTF-8"?>
+<map version="1.10" orientation="orthogonal" width="3" height="2" tilewidth="15" tileheight="15" infinite="0">
+ <properties>
+  <property name="name" value="Cave"/>
+ </properties>
+ <tileset firstgid="1" source="sprites.tsx"/>
+ <layer id="1" name="ground" width="3" height="2">
+  <data encoding="csv">
+98,2147483746,0,
+34,1073741858,98
+</data>
+ </layer>
+ <objectgroup id="2" name="objects">
+  <object id="1" name="start" type="spawn" x="16" y="31"/>
+  <object id="2" name="old man" class="npc" gid="536871040" x="15" y="30">
+   <properties>
+    <property name="dialogue" value="dialogue/old_man.dialogue.ron"/>
+   </properties>
+  </object>
+ </objectgroup>
+</map>
+"#;
+
+    const XML_TILESET: &str = r#"<?xml version="1.0" encoding="UTF-8"?>
+<tileset version="1.10" name="sprites" tilewidth="15" tileheight="15" spacing="1" tilecount="1024" columns="32">
+ <image source="Sprites.png" width="512" height="512"/>
+ <tile id="97">
+  <properties>
+   <property name="collider" type="bool" value="true"/>
+  </properties>
+ </tile>
+</tileset>
+"#;
+
+    // the sprite of every cell of a layer, None for the empty ones
+    fn sprites(map: &MapAsset, layer: usize) -> Vec<Option<usize>> {
+        map.layers[layer].cells.iter().map(|cell| cell.map(|tile| map.tiles[tile].sprite)).collect()
+    }
+
+    fn tile(map: &MapAsset, sprite: usize) -> &TileDefinition {
+        map.tiles.iter().find(|tile| tile.sprite == sprite).unwrap()
+    }
+
+    fn object<'a>(map: &'a MapAsset, name: &str) -> &'a MapObject {
+        map.objects.iter().find(|object| object.name == name).unwrap()
+    }
+
+    #[test]
+    fn json_map_is_read() {
+        let map = parse_json_map(JSON_MAP.as_bytes()).unwrap().into_map_asset("cave").unwrap();
+
+        assert_eq!(map.metadata.name, "Cave");
+        // the image layer is skipped
+        assert_eq!(map.layers.len(), 2);
+        assert_eq!(map.layers[0].width, 3);
+        // gids start at 1 so they are one more than the sprite, and flipped tiles are the same tile
+        assert_eq!(sprites(&map, 0), vec![Some(97), Some(97), None, Some(33), Some(33), Some(97)]);
+        assert_eq!(map.tiles.len(), 2);
+        assert_eq!(map.layers[0].kind, LayerKind::Ground);
+        assert_eq!(map.layers[1].kind, LayerKind::Overhead);
+
+        let wall = tile(&map, 97);
+        assert!(wall.collider);
+        assert!(wall.encounter_zone.is_none());
+        let grass = tile(&map, 33);
+        assert!(!grass.collider);
+        assert_eq!(grass.encounter_zone.as_deref(), Some("cave"));
+        // other properties are kept as they are written
+        assert_eq!(grass.properties.get("depth").map(String::as_str), Some("2"));
+        assert_eq!(grass.properties.len(), 1);
+
+        assert_eq!(map.spawn_points["start"], (1, 2));
+        assert!(matches!(
+            &object(&map, "door").kind,
+            MapObjectKind::Warp { map, spawn_point } if map == "overworld" && spawn_point == "cave"
+        ));
+        assert_eq!(object(&map, "door").position, (2, 0));
+        // a tile object is placed by its bottom left corner and its sprite is the tile
+        assert!(matches!(object(&map, "old man").kind, MapObjectKind::Npc { sprite: 127, dialogue: None }));
+        assert_eq!(object(&map, "old man").position, (1, 1));
+        assert!(matches!(&object(&map, "face").kind, MapObjectKind::Enemy { sprite: 127, enemy } if enemy == "face"));
+        assert_eq!(object(&map, "face").position, (0, 1));
+    }
+
+    #[test]
+    fn xml_map_is_read_with_an_external_tileset() {
+        let mut map = parse_xml_map(XML_MAP).unwrap();
+        assert_eq!(map.tilesets[0].source.as_deref(), Some("sprites.tsx"));
+        map.tilesets[0].tiles = parse_tileset(Path::new("sprites.tsx"), XML_TILESET.as_bytes()).unwrap();
+        let map = map.into_map_asset("cave").unwrap();
+
+        assert_eq!(map.metadata.name, "Cave");
+        // the csv is split over lines like Tiled saves it
+        assert_eq!(sprites(&map, 0), vec![Some(97), Some(97), None, Some(33), Some(33), Some(97)]);
+        assert!(tile(&map, 97).collider);
+        assert!(!tile(&map, 33).collider);
+
+        assert_eq!(map.spawn_points["start"], (1, 2));
+        assert!(matches!(
+            &object(&map, "old man").kind,
+            MapObjectKind::Npc { sprite: 127, dialogue: Some(dialogue) } if dialogue == "dialogue/old_man.dialogue.ron"
+        ));
+        assert_eq!(object(&map, "old man").position, (1, 1));
+    }
+
+    #[test]
+    fn json_tileset_is_read() {
+        let tiles = parse_tileset(
+            Path::new("sprites.tsj"),
+            br#"{"name": "sprites", "tiles": [{"id": 13, "properties": [{"name": "breakable", "type": "bool", "value": true}]}]}"#
+        ).unwrap();
+        assert_eq!(tiles[&13].get("breakable").map(String::as_str), Some("true"));
+    }
+
+    #[test]
+    fn only_csv_layers_can_be_read() {
+        let json = r#"{"tilewidth": 15, "tileheight": 15, "tilesets": [], "layers": [
+            {"type": "tilelayer", "name": "ground", "width": 1, "height": 1, "encoding": "base64", "data": "AQAAAA=="}
+        ]}"#;
+        assert!(matches!(parse_json_map(json.as_bytes()), Err(MapError::Invalid(_))));
+
+        for data in [r#"<data encoding="base64">AQAAAA==</data>"#, r#"<data encoding="base64" compression="zlib">eJxjZGBgAAAACAAC</data>"#, r#"<data><tile gid="1"/></data>"#] {
+            let xml = format!(
+                r#"<map tilewidth="15" tileheight="15"><layer name="ground" width="1" height="1">{}</layer></map>"#,
+                data
+            );
+            assert!(matches!(parse_xml_map(&xml), Err(MapError::Invalid(_))), "{}", data);
+        }
+    }
+
+    #[test]
+    fn broken_maps_are_errors() {
+        // a layer with fewer tiles than its size
+        let short = r#"{"tilewidth": 15, "tileheight": 15, "tilesets": [{"firstgid": 1}], "layers": [
+            {"type": "tilelayer", "name": "ground", "width": 2, "height": 2, "data": [1, 1, 1]}
+        ]}"#;
+        assert!(matches!(parse_json_map(short.as_bytes()).unwrap().into_map_asset("test"), Err(MapError::Invalid(_))));
+
+        // a tile from before the first tileset
+        let no_tileset = r#"{"tilewidth": 15, "tileheight": 15, "tilesets": [{"firstgid": 5}], "layers": [
+            {"type": "tilelayer", "name": "ground", "width": 1, "height": 1, "data": [2]}
+        ]}"#;
+        assert!(matches!(parse_json_map(no_tileset.as_bytes()).unwrap().into_map_asset("test"), Err(MapError::Invalid(_))));
+
+        let infinite = r#"{"tilewidth": 15, "tileheight": 15, "infinite": true, "tilesets": [], "layers": []}"#;
+        assert!(matches!(parse_json_map(infinite.as_bytes()), Err(MapError::Invalid(_))));
+
+        let unknown_kind = r#"<map tilewidth="15" tileheight="15"><layer name="ground" width="1" height="1">
+            <properties><property name="kind" value="sky"/></properties><data encoding="csv">1</data>
+        </layer></map>"#;
+        assert!(matches!(parse_xml_map(unknown_kind), Err(MapError::Invalid(_))));
+
+        let missing_property = r#"<map tilewidth="15" tileheight="15"><tileset firstgid="1"/><objectgroup>
+            <object name="door" class="warp" x="0" y="0"/>
+        </objectgroup></map>"#;
+        assert!(matches!(parse_xml_map(missing_property).unwrap().into_map_asset("test"), Err(MapError::Invalid(_))));
+    }
+
+    #[test]
+    fn parse_errors_have_the_line() {
+        assert!(matches!(parse_json_map(b"{\n\"tilewidth\": 15,\n]"), Err(MapError::Parse { line: 3, .. })));
+        assert!(matches!(parse_xml_map("<map>\n<layer>\n</map>"), Err(MapError::Parse { line: 3, .. })));
+    }
+
+    #[test]
+    fn cave_map_is_read() {
+        let map = parse_json_map(include_bytes!("../assets/maps/cave.tmj")).unwrap().into_map_asset("cave").unwrap();
+        assert!(!map.layers.is_empty());
+    }
+}
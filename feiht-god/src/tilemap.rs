@@ -1,46 +1,207 @@
-use std::{fs::File, io::{BufReader, BufRead}};
+use bevy::{prelude::*, asset::LoadState, utils::HashMap};
 
-use bevy::prelude::*;
-
-use crate::{sprites::{SpriteSheet, spawn_sprite}, TILE_SIZE, GameState};
+use crate::{sprites::{SpriteSheet, spawn_sprite}, TILE_SIZE, GameState, player::{Player, EncounterTracker}, map::{LayerKind, MapAsset, MapLoader, MapObjectKind, WorldAsset, WorldLoader}, tiled::TiledLoader, text::{BitmapText, TextAlign, TextReveal, spawn_text}, dialogue::DialogueAsset};
+use bevy_inspector_egui::Inspectable;
 
 pub struct TileMapPlugin;
 
 #[derive(Component)]
 struct Map;
 
+// one layer of the map, the tiles of the layer are its children
 #[derive(Component)]
-pub struct EncounterSpawner;
+struct TileLayer {
+    // collision layers are never shown
+    visible: bool,
+}
+
+// layers are drawn from here up, below the player
+const LAYER_Z: f32 = 100.0;
+// overhead layers are drawn from here up, above the player (z 900) but below the fadeout (z 999)
+const OVERHEAD_LAYER_Z: f32 = 950.0;
+
+// tiles where random encounters can happen, the zone picks the encounter table that is used
+#[derive(Component)]
+pub struct EncounterSpawner {
+    pub zone: String,
+}
+
+// the world file that names every map, relative to the assets folder
+const WORLD: &str = "maps/main.world.ron";
+
+pub struct WorldHandle(pub Handle<WorldAsset>);
+
+// the map that is currently being played
+#[derive(Default)]
+pub struct CurrentMap {
+    // the name of the map in the world file
+    pub name: Option<String>,
+    pub handle: Option<Handle<MapAsset>>,
+    // the map being changed to, it becomes the current map once it has loaded and been spawned
+    loading: Option<LoadingMap>,
+}
+
+struct LoadingMap {
+    name: String,
+    handle: Handle<MapAsset>,
+    // where the player is put once the map has loaded
+    spawn: PlayerSpawn,
+}
+
+// the tiles and objects of the current map by the cell they are in, so checks against
+// the map only have to look at the cells around the player instead of every tile
+#[derive(Default)]
+pub struct TileGrid {
+    cells: HashMap<IVec2, Vec<Entity>>,
+}
+
+impl TileGrid {
+    // the cell a world position is in, x goes right and y goes down like in the map file
+    pub fn cell(translation: Vec3) -> IVec2 {
+        IVec2::new(
+            (translation.x / TILE_SIZE).round() as i32,
+            (-translation.y / TILE_SIZE).round() as i32,
+        )
+    }
+
+    fn insert(&mut self, cell: IVec2, entity: Entity) {
+        self.cells.entry(cell).or_default().push(entity);
+    }
+
+    // for something taken off the map before the map is unloaded, like a bush that was cut down
+    pub fn remove(&mut self, cell: IVec2, entity: Entity) {
+        if let Some(entities) = self.cells.get_mut(&cell) {
+            entities.retain(|&other| other != entity);
+            if entities.is_empty() {
+                self.cells.remove(&cell);
+            }
+        }
+    }
+
+    // every entity in the cell of the translation
+    pub fn at(&self, translation: Vec3) -> impl Iterator<Item = Entity> + '_ {
+        self.cells.get(&TileGrid::cell(translation)).into_iter().flatten().copied()
+    }
+
+    // every entity in the cell of the translation and the 8 cells around it, anything
+    // the size of a tile at the translation can only touch entitys in these cells
+    pub fn around(&self, translation: Vec3) -> impl Iterator<Item = Entity> + '_ {
+        let center = TileGrid::cell(translation);
+        (-1..=1)
+            .flat_map(move |y| (-1..=1).map(move |x| center + IVec2::new(x, y)))
+            .filter_map(|cell| self.cells.get(&cell))
+            .flatten()
+            .copied()
+    }
+}
+
+// where the player is put on a map
+#[derive(Clone, Debug)]
+pub enum PlayerSpawn {
+    // one of the named spawn points of the map
+    Point(String),
+    // a world position, for putting the player back where they were when the game was saved
+    Position(Vec2),
+}
+
+// sent to unload the current map and load another one, the player is put at the spawn
+#[derive(Clone, Debug)]
+pub struct ChangeMap {
+    pub map: String,
+    pub spawn: PlayerSpawn,
+}
 
 #[derive(Component)]
 pub struct TileCollider;
 
+// one of the custom properties from the tile legend
+#[derive(Inspectable, Clone, Default)]
+pub struct TileProperty {
+    pub name: String,
+    pub value: String,
+}
+
+// the custom properties of a tile, only tiles that have any get this component
+#[derive(Component, Inspectable)]
+pub struct TileProperties {
+    pub properties: Vec<TileProperty>,
+}
+
+// a named place on the map the player can be put
+#[derive(Component, Inspectable)]
+pub struct SpawnPoint {
+    pub name: String,
+}
+
+// stepping on a warp takes the player to a spawn point on another map
+#[derive(Component, Inspectable)]
+pub struct Warp {
+    pub map: String,
+    pub spawn_point: String,
+}
+
+// a character standing on the map
+#[derive(Component)]
+pub struct Npc {
+    // what the npc says when the player talks to them
+    pub dialogue: Option<Handle<DialogueAsset>>,
+}
+
+// an enemy standing on the map, it is given its Stats from the EnemyDatabase once it is spawned
+#[derive(Component)]
+pub struct OverworldEnemy {
+    // key of the definition in the EnemyDatabase
+    pub id: String,
+}
+
+// the name of the map shown at the top of the screen after a warp, despawned when the timer finishes
+#[derive(Component)]
+struct MapBanner(Timer);
+
+// how long the name of the map is shown for in seconds
+const BANNER_TIME: f32 = 2.5;
+
 impl Plugin for TileMapPlugin {
     fn build(&self, app: &mut App) {
         app
-            .add_startup_system(create_simple_map)
+            .add_asset::<MapAsset>()
+            .init_asset_loader::<MapLoader>()
+            .init_asset_loader::<TiledLoader>()
+            .add_asset::<WorldAsset>()
+            .init_asset_loader::<WorldLoader>()
+            .add_event::<ChangeMap>()
+            .init_resource::<CurrentMap>()
+            .init_resource::<TileGrid>()
+            .add_startup_system(load_world)
+            // maps are changed and spawned whenever they finish loading no matter the state,
+            // the first map is changed to when a game is started from the title screen
+            .add_system(change_map.label("change_map"))
+            .add_system(spawn_current_map.after("change_map"))
+            .add_system(despawn_map_banner)
             .add_system_set(
                 SystemSet::on_enter(GameState::Overworld).with_system(show_map))
-            .add_system_set(
-                SystemSet::on_exit(GameState::Overworld).with_system(hide_map))
+            .add_system_set(SystemSet::on_exit(GameState::Overworld)
+                .with_system(hide_map)
+                .with_system(despawn_map_banners)
+            )
             
             ;
     }
 }
 
 fn hide_map(
-    // query for the children of the Map, this will be used to check if 
-    // the map has any children
-    children_query: Query<&mut Children, With<Map>>,
+    // query for the children of every layer of the Map, this will be used to
+    // check if the layers have any tiles
+    children_query: Query<&Children, With<TileLayer>>,
     // query for the visibility component, this will be used to toggle
-    // off the visibility of the maps children (if the map has any)
-    mut child_visibility_query: Query<&mut Visibility, Without<Map>>
+    // off the visibility of the tiles (if the layers have any)
+    mut child_visibility_query: Query<&mut Visibility, Without<TileLayer>>
 ) {
-    // if there is children to the map
-    if let Ok(children) = children_query.get_single() {
-        // for each child
+    // for each layer
+    for children in children_query.iter() {
+        // for each tile
         for child in children.iter() {
-            // get the specific child entitys Visibility component from the query,
+            // get the specific tile entitys Visibility component from the query,
             // (since we queryed for all entitys with the Visibility component)
             if let Ok(mut child_vis) = child_visibility_query.get_mut(*child) {
                 child_vis.is_visible = false;
@@ -50,62 +211,403 @@ fn hide_map(
 }
 
 fn show_map(
-    // query for the children of the Map, this will be used to check if 
-    // the map has any children
-    children_query: Query<&mut Children, With<Map>>,
+    // query for the children of every layer of the Map (to check if the layers have
+    // any tiles) and the layer itself (to check if the layer should be shown)
+    children_query: Query<(&Children, &TileLayer)>,
     // query for the visibility component, this will be used to toggle
-    // on the visibility of the maps children (if the map has any)
-    mut child_visibility_query: Query<&mut Visibility, Without<Map>>
+    // on the visibility of the tiles (if the layers have any)
+    mut child_visibility_query: Query<&mut Visibility, Without<TileLayer>>
 ) {
-    // if there is children to the map
-    if let Ok(children) = children_query.get_single() {
-        // for each child
+    // for each layer
+    for (children, layer) in children_query.iter() {
+        // for each tile
         for child in children.iter() {
-            // get the specific child entitys Visibility component from the query,
+            // get the specific tile entitys Visibility component from the query,
             // (since we queryed for all entitys with the Visibility component)
             if let Ok(mut child_vis) = child_visibility_query.get_mut(*child) {
-                child_vis.is_visible = true;
+                child_vis.is_visible = layer.visible;
+            }
+        }
+    }
+}
+
+fn load_world(mut commands: Commands, assets: Res<AssetServer>) {
+    commands.insert_resource(WorldHandle(assets.load(WORLD)));
+}
+
+fn change_map(
+    mut change_map_events: EventReader<ChangeMap>,
+    // the world file has the path of every map
+    world_handle: Res<WorldHandle>,
+    worlds: Res<Assets<WorldAsset>>,
+    mut current_map: ResMut<CurrentMap>,
+    assets: Res<AssetServer>
+) {
+    let world = match worlds.get(&world_handle.0) {
+        Some(world) => world,
+        None => return,
+    };
+
+    for event in change_map_events.iter() {
+        let path = match world.maps.get(&event.map) {
+            Some(path) => path,
+            None => {
+                warn!("There is no map \"{}\" in the world file", event.map);
+                continue;
+            }
+        };
+
+        // the old map stays the current map (and its entity stays) until the new map is spawned over it
+        current_map.loading = Some(LoadingMap {
+            name: event.map.clone(),
+            handle: assets.load(path.as_str()),
+            spawn: event.spawn.clone(),
+        });
+    }
+}
+
+#[allow(clippy::too_many_arguments)]
+fn spawn_current_map(
+    // commands are used to despawn the old map and spawn the new one
+    mut commands: Commands,
+    maps: Res<Assets<MapAsset>>,
+    mut current_map: ResMut<CurrentMap>,
+    // the old map is despawned before the new one is spawned
+    map_query: Query<Entity, With<Map>>,
+    // the player is moved to the spawn point once the map has loaded, and can move again after a warp
+    mut player_query: Query<(&mut Transform, &mut Player, &mut EncounterTracker)>,
+    // the grid is filled with the tiles of the new map
+    mut grid: ResMut<TileGrid>,
+    // the name of the map is shown in front of the camera after a warp
+    camera_query: Query<Entity, With<Camera>>,
+    banner_query: Query<Entity, With<MapBanner>>,
+    // the tiles are only visible while in the overworld
+    state: Res<State<GameState>>,
+    sprites: Res<SpriteSheet>,
+    // the dialogue of the npcs is loaded with the map
+    assets: Res<AssetServer>
+) {
+    // the map is only spawned once, when it is first changed to and has loaded
+    let loading = match current_map.loading.take() {
+        Some(loading) => loading,
+        None => return,
+    };
+    if assets.get_load_state(&loading.handle) == LoadState::Failed {
+        // the player was stopped for the warp, they carry on from where they are on the old map
+        warn!("Map \"{}\" could not be loaded", loading.name);
+        player_query.single_mut().1.is_active = true;
+        return;
+    }
+    let map = match maps.get(&loading.handle) {
+        Some(map) => map,
+        None => {
+            current_map.loading = Some(loading);
+            return;
+        }
+    };
+    current_map.name = Some(loading.name.clone());
+    current_map.handle = Some(loading.handle.clone());
+
+    for entity in map_query.iter() {
+        commands.entity(entity).despawn_recursive();
+    }
+
+    info!("Spawning map \"{}\"", map.metadata.name);
+    spawn_map(&mut commands, &sprites, &assets, &mut grid, map, *state.current() == GameState::Overworld);
+
+    let (mut transform, mut player, mut encounter_tracker) = player_query.single_mut();
+    let position = match loading.spawn {
+        PlayerSpawn::Point(spawn_point) => match map.spawn_points.get(&spawn_point) {
+            Some(&(x, y)) => Some(tile_translation(x, y, 0.0).truncate()),
+            None => {
+                warn!("Map \"{}\" has no \"{}\" spawn point", map.metadata.name, spawn_point);
+                None
             }
+        },
+        PlayerSpawn::Position(position) => Some(position),
+    };
+    if let Some(position) = position {
+        transform.translation.x = position.x;
+        transform.translation.y = position.y;
+        // so the jump to the spawn point is not counted as walking
+        encounter_tracker.reset_position(transform.translation);
+    }
+    player.is_active = true;
+
+    // a warp before the last banner is gone replaces it
+    for banner in banner_query.iter() {
+        commands.entity(banner).despawn_recursive();
+    }
+    if let Some(camera) = camera_query.iter().next() {
+        let banner = spawn_text(
+            &mut commands,
+            BitmapText {
+                text: map.metadata.name.clone(),
+                size: 0.08,
+                color: Color::WHITE,
+                align: TextAlign::Center,
+                max_width: None,
+            },
+            // in front of the map but behind the fadeout
+            Vec3::new(0.0, 0.85, -1.0)
+        );
+        commands.entity(banner)
+            .insert(TextReveal::new(20.0))
+            .insert(MapBanner(Timer::from_seconds(BANNER_TIME, false)));
+        commands.entity(camera).add_child(banner);
+    }
+}
+
+fn despawn_map_banner(
+    mut commands: Commands,
+    mut banner_query: Query<(Entity, &mut MapBanner)>,
+    // the banner stays up while the game is paused
+    state: Res<State<GameState>>,
+    time: Res<Time>
+) {
+    if *state.current() == GameState::Pause { return; }
+
+    for (entity, mut banner) in banner_query.iter_mut() {
+        banner.0.tick(time.delta());
+        if banner.0.finished() {
+            commands.entity(entity).despawn_recursive();
         }
     }
 }
 
-fn create_simple_map(mut commands: Commands, sprites: Res<SpriteSheet>) {
-    let file = File::open("assets/map.txt")
-        .expect("Map is missing!");
-    let mut tiles = Vec::new();
-
-    for (y, line) in BufReader::new(file).lines().enumerate() {
-        if let Ok(line) = line {
-            for (x, char) in line.chars().enumerate() {
-                let tile = spawn_sprite(
-                    &mut commands, 
-                    &sprites, 
-                    char as usize, 
-                    Vec3::new(x as f32 * TILE_SIZE, -(y as f32) * TILE_SIZE, 100.0),
-                    1.0
-                );
-                
-                let mut curr_char = 'a' as usize;
-                while curr_char < 'z' as usize {
-                    if curr_char == char as usize {
-                        commands.entity(tile).insert(TileCollider);
-                    }
-
-                    curr_char += 1;
-                }
-                if char == '!' {
-                    commands.entity(tile).insert(EncounterSpawner);
-                }
-
-                tiles.push(tile);
+// the banner is only for the overworld, it is not left up over combat or the title screen
+fn despawn_map_banners(mut commands: Commands, banner_query: Query<Entity, With<MapBanner>>) {
+    for entity in banner_query.iter() {
+        commands.entity(entity).despawn_recursive();
+    }
+}
+
+// world position of the tile at x, y (in tiles from the top left of the map)
+fn tile_translation(x: i32, y: i32, z: f32) -> Vec3 {
+    Vec3::new(x as f32 * TILE_SIZE, -(y as f32) * TILE_SIZE, z)
+}
+
+fn spawn_map(commands: &mut Commands, sprites: &SpriteSheet, assets: &AssetServer, grid: &mut TileGrid, map: &MapAsset, visible: bool) -> Entity {
+    let mut layers = Vec::new();
+    *grid = TileGrid::default();
+
+    for (index, layer) in map.layers.iter().enumerate() {
+        let mut tiles = Vec::new();
+        // every layer is drawn slightly above the one before it,
+        // overhead layers are drawn above the player
+        let z = match layer.kind {
+            LayerKind::Overhead => OVERHEAD_LAYER_Z + index as f32,
+            _ => LAYER_Z + index as f32,
+        };
+        let layer_visible = layer.kind != LayerKind::Collision;
+
+        for (x, y, index) in layer.iter_tiles() {
+            let definition = &map.tiles[index];
+
+            let tile = spawn_sprite(
+                commands, 
+                sprites, 
+                definition.sprite, 
+                tile_translation(x as i32, y as i32, z),
+                1.0
+            );
+            commands.entity(tile).insert(Visibility { is_visible: visible && layer_visible });
+
+            // every tile of a collision layer blocks the player
+            if definition.collider || layer.kind == LayerKind::Collision {
+                commands.entity(tile).insert(TileCollider);
+            }
+            if let Some(zone) = &definition.encounter_zone {
+                commands.entity(tile).insert(EncounterSpawner {
+                    zone: zone.clone(),
+                });
             }
+            if !definition.properties.is_empty() {
+                let properties = definition.properties
+                    .iter()
+                    .map(|(name, value)| TileProperty { name: name.clone(), value: value.clone() })
+                    .collect();
+                commands.entity(tile).insert(TileProperties { properties });
+            }
+
+            grid.insert(IVec2::new(x as i32, y as i32), tile);
+            tiles.push(tile);
         }
+
+        let layer = commands.spawn()
+            .insert(TileLayer { visible: layer_visible })
+            .insert(Name::new(layer.name.clone()))
+            .insert(Transform::default())
+            .insert(GlobalTransform::default())
+            .push_children(&tiles)
+            .id();
+        layers.push(layer);
+    }
+
+    // spawn points and objects go in their own layer above the tiles
+    let mut objects = Vec::new();
+    let objects_z = LAYER_Z + map.layers.len() as f32;
+
+    for (name, &(x, y)) in map.spawn_points.iter() {
+        let spawn_point = commands.spawn()
+            .insert(SpawnPoint { name: name.clone() })
+            .insert(Name::new(format!("Spawn Point {}", name)))
+            .insert(Transform::from_translation(tile_translation(x, y, objects_z)))
+            .insert(GlobalTransform::default())
+            .id();
+        objects.push(spawn_point);
+    }
+
+    for object in map.objects.iter() {
+        let (x, y) = object.position;
+        let entity = match &object.kind {
+            MapObjectKind::Warp { map, spawn_point } => commands.spawn()
+                .insert(Warp { map: map.clone(), spawn_point: spawn_point.clone() })
+                .insert(Transform::from_translation(tile_translation(x, y, objects_z)))
+                .insert(GlobalTransform::default())
+                .id(),
+            MapObjectKind::Npc { sprite, dialogue } => {
+                let npc = spawn_sprite(commands, sprites, *sprite, tile_translation(x, y, objects_z), 1.0);
+                commands.entity(npc)
+                    .insert(Npc { dialogue: dialogue.as_ref().map(|path| assets.load(path.as_str())) })
+                    // npcs block the player like walls do
+                    .insert(TileCollider)
+                    .insert(Visibility { is_visible: visible });
+                npc
+            }
+            MapObjectKind::Enemy { sprite, enemy } => {
+                let overworld_enemy = spawn_sprite(commands, sprites, *sprite, tile_translation(x, y, objects_z), 1.0);
+                commands.entity(overworld_enemy)
+                    .insert(OverworldEnemy { id: enemy.clone() })
+                    // enemys block the player like npcs, the player is hurt by walking into them
+                    .insert(TileCollider)
+                    .insert(Visibility { is_visible: visible });
+                overworld_enemy
+            }
+        };
+        commands.entity(entity).insert(Name::new(object.name.clone()));
+        grid.insert(IVec2::new(x, y), entity);
+        objects.push(entity);
     }
+
+    let objects_layer = commands.spawn()
+        .insert(TileLayer { visible: true })
+        .insert(Name::new("Objects"))
+        .insert(Transform::default())
+        .insert(GlobalTransform::default())
+        .push_children(&objects)
+        .id();
+    layers.push(objects_layer);
+
     commands.spawn()
         .insert(Map)
-        .insert(Name::new("Map"))
+        .insert(Name::new(map.metadata.name.clone()))
         .insert(Transform::default())
         .insert(GlobalTransform::default())
-        .push_children(&tiles);
-}
\ No newline at end of file
+        .push_children(&layers)
+        .id()
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::{Duration, Instant};
+
+    use bevy::sprite::collide_aabb::collide;
+    use rand::{rngs::StdRng, Rng, SeedableRng};
+
+    use super::*;
+    use crate::collision::{PLAYER_SIZE, WALL_SIZE};
+
+    // tiles across and down, much bigger than any map in the game
+    const MAP_SIZE: i32 = 300;
+
+    // a wall on every third tile of a large map, with the translation of every wall by its entity id
+    fn large_map() -> (TileGrid, Vec<Vec3>) {
+        let mut grid = TileGrid::default();
+        let mut walls = Vec::new();
+        for y in 0..MAP_SIZE {
+            for x in 0..MAP_SIZE {
+                if (x + y) % 3 != 0 { continue; }
+                grid.insert(IVec2::new(x, y), Entity::from_raw(walls.len() as u32));
+                walls.push(tile_translation(x, y, 0.0));
+            }
+        }
+        (grid, walls)
+    }
+
+    // the player at random places on the map, between tiles as well as on them
+    fn player_positions() -> Vec<Vec3> {
+        let mut rng = StdRng::seed_from_u64(10);
+        (0..200)
+            .map(|_| Vec3::new(
+                rng.gen_range(0.0..MAP_SIZE as f32) * TILE_SIZE,
+                -rng.gen_range(0.0..MAP_SIZE as f32) * TILE_SIZE,
+                0.0,
+            ))
+            .collect()
+    }
+
+    fn touching(player: Vec3, wall: Vec3) -> bool {
+        collide(player, Vec2::splat(PLAYER_SIZE), wall, Vec2::splat(WALL_SIZE)).is_some()
+    }
+
+    // the way walls were found before the grid, every wall on the map is checked
+    fn linear_scan(walls: &[Vec3], player: Vec3) -> Vec<u32> {
+        (0..walls.len() as u32).filter(|&id| touching(player, walls[id as usize])).collect()
+    }
+
+    fn grid_lookup(grid: &TileGrid, walls: &[Vec3], player: Vec3) -> Vec<u32> {
+        let mut ids: Vec<u32> = grid
+            .around(player)
+            .map(|entity| entity.id())
+            .filter(|&id| touching(player, walls[id as usize]))
+            .collect();
+        ids.sort_unstable();
+        ids
+    }
+
+    #[test]
+    fn grid_finds_the_same_walls_as_a_linear_scan() {
+        let (grid, walls) = large_map();
+        for player in player_positions() {
+            assert_eq!(grid_lookup(&grid, &walls, player), linear_scan(&walls, player), "player at {}", player);
+            // never more than the 9 cells around the player are looked at
+            assert!(grid.around(player).count() <= 9);
+        }
+    }
+
+    // a wall clock timing that a busy machine or a debug build can make fail, so it only runs when asked for
+    #[test]
+    #[ignore = "timing, run with --release -- --ignored"]
+    fn grid_is_faster_than_a_linear_scan() {
+        let (grid, walls) = large_map();
+        let positions = player_positions();
+        let time = |find: &dyn Fn(Vec3) -> Vec<u32>| -> Duration {
+            let start = Instant::now();
+            for &player in positions.iter() {
+                std::hint::black_box(find(player));
+            }
+            start.elapsed()
+        };
+
+        let linear = time(&|player| linear_scan(&walls, player));
+        let lookup = time(&|player| grid_lookup(&grid, &walls, player));
+        // the grid is hundreds of times faster on a map this size, 10 leaves room for a busy machine
+        assert!(lookup * 10 < linear, "linear scan {:?}, grid {:?}", linear, lookup);
+    }
+
+    #[test]
+    fn removed_entitys_are_not_found_again() {
+        let mut grid = TileGrid::default();
+        let (bush, wall) = (Entity::from_raw(0), Entity::from_raw(1));
+        grid.insert(IVec2::new(2, 3), bush);
+        grid.insert(IVec2::new(2, 3), wall);
+
+        let translation = tile_translation(2, 3, 0.0);
+        grid.remove(TileGrid::cell(translation), bush);
+        assert_eq!(grid.around(translation).collect::<Vec<_>>(), vec![wall]);
+        grid.remove(TileGrid::cell(translation), wall);
+        assert_eq!(grid.around(translation).count(), 0);
+        assert!(grid.cells.is_empty());
+    }
+}